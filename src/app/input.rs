@@ -0,0 +1,332 @@
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// The text being typed in the input area and the cursor within it.
+///
+/// The cursor is a byte offset that is always on a grapheme boundary, so moving and deleting
+/// treat e.g. "é" written as "e" plus a combining accent, or a flag emoji, as one character.
+/// Words are runs of non-whitespace, as with readline's unix-word-rubout.
+///
+/// The text may have several lines, e.g. a message of a few paragraphs. Moving to and deleting
+/// up to the start or end are then within the cursor's line.
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Input {
+    text: String,
+    cursor: usize,
+}
+
+impl Input {
+    // the cursor is placed at the end of the text
+    pub fn new(text: String) -> Self {
+        let cursor = text.len();
+        Self { text, cursor }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn starts_with(&self, prefix: char) -> bool {
+        self.text.starts_with(prefix)
+    }
+
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.text.split('\n').count()
+    }
+
+    pub fn is_cursor_on_first_line(&self) -> bool {
+        !self.before_cursor().contains('\n')
+    }
+
+    pub fn is_cursor_on_last_line(&self) -> bool {
+        !self.text[self.cursor..].contains('\n')
+    }
+
+    // replace the whole text, with the cursor at the end
+    pub fn set(&mut self, text: String) {
+        *self = Self::new(text);
+    }
+
+    // replace the text before the cursor, e.g. with its completion, keeping the text after it
+    pub fn set_before_cursor(&mut self, text: String) {
+        self.text.replace_range(..self.cursor, &text);
+        self.cursor = text.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    // take the text, leaving the input empty
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    // the text as it would be after inserting c at the cursor, used to validate the next char
+    pub fn with_inserted(&self, c: char) -> String {
+        self.with_inserted_str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    pub fn with_inserted_str(&self, s: &str) -> String {
+        let mut text = self.text.clone();
+        text.insert_str(self.cursor, s);
+        text
+    }
+
+    // editing
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+        // c may combine with the grapheme after it, e.g. a combining accent typed before a letter
+        self.cursor = self.grapheme_end(self.cursor);
+    }
+
+    // e.g. a paste, the cursor goes after it
+    pub fn insert_str(&mut self, s: &str) {
+        self.text.insert_str(self.cursor, s);
+        self.cursor = self.grapheme_end(self.cursor + s.len());
+    }
+
+    pub fn delete_prev_char(&mut self) {
+        let start = self.prev_grapheme_start(self.cursor);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    pub fn delete_next_char(&mut self) {
+        let end = self.next_grapheme_end(self.cursor);
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    // Ctrl-W, delete back to the start of the word before the cursor
+    pub fn delete_prev_word(&mut self) {
+        let start = self.prev_word_start(self.cursor);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    // Ctrl-U, at the start of a line it joins the line with the one before
+    pub fn delete_to_start(&mut self) {
+        let start = self.line_start(self.cursor);
+        if start == self.cursor {
+            self.delete_prev_char();
+            return;
+        }
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    // Ctrl-K, at the end of a line it joins the line with the one after
+    pub fn delete_to_end(&mut self) {
+        let end = self.line_end(self.cursor);
+        if end == self.cursor {
+            self.delete_next_char();
+            return;
+        }
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    // cursor movement
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.prev_grapheme_start(self.cursor);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = self.next_grapheme_end(self.cursor);
+    }
+
+    pub fn move_word_left(&mut self) {
+        self.cursor = self.prev_word_start(self.cursor);
+    }
+
+    pub fn move_word_right(&mut self) {
+        self.cursor = self.next_word_end(self.cursor);
+    }
+
+    pub fn move_to_start(&mut self) {
+        self.cursor = self.line_start(self.cursor);
+    }
+
+    pub fn move_to_end(&mut self) {
+        self.cursor = self.line_end(self.cursor);
+    }
+
+    // to the same column of the line above, or its end if it is shorter
+    pub fn move_up(&mut self) {
+        let start = self.line_start(self.cursor);
+        if start == 0 {
+            return;
+        }
+        let column = self.text[start..self.cursor].width();
+        let prev_start = self.line_start(start - 1);
+        self.cursor = self.offset_at_column(prev_start, start - 1, column);
+    }
+
+    // to the same column of the line below, or its end if it is shorter
+    pub fn move_down(&mut self) {
+        let end = self.line_end(self.cursor);
+        if end == self.text.len() {
+            return;
+        }
+        let column = self.text[self.line_start(self.cursor)..self.cursor].width();
+        let next_end = self.line_end(end + 1);
+        self.cursor = self.offset_at_column(end + 1, next_end, column);
+    }
+
+    // the cursor's line, and its column in terminal columns
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let before_cursor = self.before_cursor();
+        let row = before_cursor.matches('\n').count();
+        let column = before_cursor[self.line_start(self.cursor)..].width();
+        (row, column)
+    }
+
+    // grapheme, word and line boundaries, as byte offsets
+
+    fn line_start(&self, offset: usize) -> usize {
+        self.text[..offset].rfind('\n').map_or(0, |index| index + 1)
+    }
+
+    fn line_end(&self, offset: usize) -> usize {
+        self.text[offset..]
+            .find('\n')
+            .map_or(self.text.len(), |index| offset + index)
+    }
+
+    // the grapheme boundary between start and end closest to column, without going past it
+    fn offset_at_column(&self, start: usize, end: usize, column: usize) -> usize {
+        let mut width = 0;
+        for (index, grapheme) in self.text[start..end].grapheme_indices(true) {
+            width += grapheme.width();
+            if width > column {
+                return start + index;
+            }
+        }
+        end
+    }
+
+    fn prev_grapheme_start(&self, offset: usize) -> usize {
+        self.text[..offset]
+            .grapheme_indices(true)
+            .next_back()
+            .map(|(start, _)| start)
+            .unwrap_or(0)
+    }
+
+    fn next_grapheme_end(&self, offset: usize) -> usize {
+        self.text[offset..]
+            .graphemes(true)
+            .next()
+            .map(|grapheme| offset + grapheme.len())
+            .unwrap_or(offset)
+    }
+
+    // the end of the grapheme containing offset, or offset if it is already a boundary
+    fn grapheme_end(&self, offset: usize) -> usize {
+        self.text
+            .grapheme_indices(true)
+            .map(|(start, grapheme)| start + grapheme.len())
+            .find(|end| *end >= offset)
+            .unwrap_or(offset)
+    }
+
+    fn prev_word_start(&self, offset: usize) -> usize {
+        let before = self.text[..offset].trim_end();
+        before
+            .rfind(char::is_whitespace)
+            .map(|index| index + before[index..].chars().next().map_or(0, char::len_utf8))
+            .unwrap_or(0)
+    }
+
+    fn next_word_end(&self, offset: usize) -> usize {
+        let after = &self.text[offset..];
+        let word_start = after.len() - after.trim_start().len();
+        after[word_start..]
+            .find(char::is_whitespace)
+            .map(|index| offset + word_start + index)
+            .unwrap_or(self.text.len())
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "é" written as "e" plus a combining acute accent
+    const E_ACUTE: &str = "e\u{301}";
+
+    #[test]
+    fn cursor_moves_over_combining_characters() {
+        let mut input = Input::new(format!("a{E_ACUTE}b"));
+        input.move_left();
+        assert_eq!(input.before_cursor(), format!("a{E_ACUTE}"));
+        input.move_left();
+        assert_eq!(input.before_cursor(), "a");
+        input.move_right();
+        assert_eq!(input.before_cursor(), format!("a{E_ACUTE}"));
+        assert_eq!(input.cursor_position(), (0, 2));
+    }
+
+    #[test]
+    fn delete_removes_whole_grapheme() {
+        let mut input = Input::new(format!("a{E_ACUTE}"));
+        input.delete_prev_char();
+        assert_eq!(input.as_str(), "a");
+
+        let mut input = Input::new(format!("{E_ACUTE}b"));
+        input.move_to_start();
+        input.delete_next_char();
+        assert_eq!(input.as_str(), "b");
+    }
+
+    #[test]
+    fn combining_character_joins_grapheme_before_cursor() {
+        let mut input = Input::new("e".to_string());
+        input.insert('\u{301}');
+        assert_eq!(input.as_str(), E_ACUTE);
+        input.move_left();
+        assert_eq!(input.before_cursor(), "");
+    }
+
+    #[test]
+    fn words_and_lines_are_deleted_up_to_cursor() {
+        let mut input = Input::new("boo to you".to_string());
+        input.delete_prev_word();
+        assert_eq!(input.as_str(), "boo to ");
+        input.move_word_left();
+        input.delete_to_end();
+        assert_eq!(input.as_str(), "boo ");
+        input.delete_to_start();
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn cursor_moves_between_lines_by_column() {
+        let mut input = Input::new("boo\nb".to_string());
+        input.move_up();
+        assert_eq!(input.cursor_position(), (0, 1));
+        input.move_to_end();
+        input.move_down();
+        assert_eq!(input.cursor_position(), (1, 1));
+    }
+}
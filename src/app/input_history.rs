@@ -0,0 +1,212 @@
+use crate::history::MAX_INPUTS;
+
+/// The lines entered in the input, messages and commands, for recalling them with Ctrl-P and
+/// Ctrl-N or finding them with a reverse incremental search (Ctrl-R). Lines of previous sessions
+/// are loaded from the history database when it is opened, whether the chat history is enabled
+/// or not.
+///
+/// The line being typed when recalling starts is kept as the draft, and given back when recalling
+/// past the newest line. Likewise the search gives back the input as it was if cancelled.
+
+#[derive(Debug, Default)]
+pub struct InputHistory {
+    entries: Vec<String>, // oldest first
+    recall_index: Option<usize>,
+    draft: Option<String>,
+    search: Option<InputSearch>,
+}
+
+#[derive(Debug, Default)]
+pub struct InputSearch {
+    pub query: String,
+    pub match_index: Option<usize>,
+    input_before: String,
+}
+
+impl InputHistory {
+    pub fn new(entries: Vec<String>) -> Self {
+        Self {
+            entries,
+            ..Default::default()
+        }
+    }
+
+    // returns false if the line is the same as the last one, which is not kept twice
+    pub fn push(&mut self, line: String) -> bool {
+        self.recall_index = None;
+        self.draft = None;
+
+        if self.entries.last() == Some(&line) {
+            return false;
+        }
+        self.entries.push(line);
+        if self.entries.len() > MAX_INPUTS {
+            self.entries.remove(0);
+        }
+        true
+    }
+
+    // recall
+
+    // the line before the recalled one, input is kept as the draft when recalling starts
+    pub fn recall_prev(&mut self, input: &str) -> Option<String> {
+        let index = match self.recall_index {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = Some(input.to_string());
+                self.entries.len() - 1
+            }
+            Some(0) => return None,
+            Some(index) => index - 1,
+        };
+        self.recall_index = Some(index);
+        self.entries.get(index).cloned()
+    }
+
+    // the line after the recalled one, or the draft past the newest line
+    pub fn recall_next(&mut self) -> Option<String> {
+        let index = self.recall_index?;
+        if index + 1 < self.entries.len() {
+            self.recall_index = Some(index + 1);
+            self.entries.get(index + 1).cloned()
+        } else {
+            self.recall_index = None;
+            Some(self.draft.take().unwrap_or_default())
+        }
+    }
+
+    // search
+
+    pub fn get_search(&self) -> Option<&InputSearch> {
+        self.search.as_ref()
+    }
+
+    pub fn get_search_match(&self) -> Option<&str> {
+        let index = self.search.as_ref()?.match_index?;
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    pub fn start_search(&mut self, input: &str) {
+        self.search = Some(InputSearch {
+            input_before: input.to_string(),
+            ..Default::default()
+        });
+    }
+
+    // find an older match, the current one is kept if there is none
+    pub fn search_older(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let before = search.match_index.unwrap_or(self.entries.len());
+        if let Some(index) = find_match(&self.entries, &search.query, before) {
+            search.match_index = Some(index);
+        }
+    }
+
+    // the current match is kept if it still matches
+    pub fn push_search_char(&mut self, c: char) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        search.query.push(c);
+        let before = search
+            .match_index
+            .map(|index| index + 1)
+            .unwrap_or(self.entries.len());
+        search.match_index = find_match(&self.entries, &search.query, before);
+    }
+
+    // with a shorter query, search again from the newest line
+    pub fn pop_search_char(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        search.query.pop();
+        search.match_index = find_match(&self.entries, &search.query, self.entries.len());
+    }
+
+    // the matching line for the input, recalling continues from it
+    pub fn accept_search(&mut self) -> Option<String> {
+        let search = self.search.take()?;
+        let Some(index) = search.match_index else {
+            return Some(search.input_before);
+        };
+        if self.recall_index.is_none() {
+            self.draft = Some(search.input_before);
+        }
+        self.recall_index = Some(index);
+        self.entries.get(index).cloned()
+    }
+
+    // the input as it was before searching
+    pub fn cancel_search(&mut self) -> Option<String> {
+        self.search.take().map(|search| search.input_before)
+    }
+}
+
+// index of the newest entry before `before` containing query
+fn find_match(entries: &[String], query: &str, before: usize) -> Option<usize> {
+    if query.is_empty() {
+        return None;
+    }
+    entries[..before.min(entries.len())]
+        .iter()
+        .rposition(|entry| entry.contains(query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[&str]) -> InputHistory {
+        InputHistory::new(entries.iter().map(|entry| entry.to_string()).collect())
+    }
+
+    #[test]
+    fn recall_goes_back_and_returns_the_draft() {
+        let mut history = history(&["first", "second"]);
+        assert_eq!(history.recall_prev("draft").as_deref(), Some("second"));
+        assert_eq!(history.recall_prev("second").as_deref(), Some("first"));
+        assert_eq!(history.recall_prev("first"), None);
+        assert_eq!(history.recall_next().as_deref(), Some("second"));
+        assert_eq!(history.recall_next().as_deref(), Some("draft"));
+        assert_eq!(history.recall_next(), None);
+    }
+
+    #[test]
+    fn repeated_line_is_kept_once() {
+        let mut history = history(&["boo"]);
+        assert!(!history.push("boo".to_string()));
+        assert!(history.push("/quit".to_string()));
+    }
+
+    #[test]
+    fn search_finds_older_matches() {
+        let mut history = history(&["boo", "/join haunted", "boo to you"]);
+        history.start_search("draft");
+        history.push_search_char('b');
+        history.push_search_char('o');
+        assert_eq!(history.get_search_match(), Some("boo to you"));
+        history.search_older();
+        assert_eq!(history.get_search_match(), Some("boo"));
+        history.search_older();
+        assert_eq!(history.get_search_match(), Some("boo"));
+
+        assert_eq!(history.accept_search().as_deref(), Some("boo"));
+        assert_eq!(history.recall_next().as_deref(), Some("/join haunted"));
+    }
+
+    #[test]
+    fn cancelled_search_returns_the_input() {
+        let mut history = history(&["boo"]);
+        history.start_search("draft");
+        history.push_search_char('b');
+        assert_eq!(history.cancel_search().as_deref(), Some("draft"));
+        assert!(!history.is_searching());
+    }
+}
@@ -1,25 +1,76 @@
 use chrono::{DateTime, Utc};
 use std::fmt;
 
-// TODO: implement SystemError
 #[derive(Clone, Debug)]
 pub enum Message {
-    SystemInternal(String),
-    SystemPublic(String),
-    // SystemError(String),
+    SystemError(SystemMessage),
+    SystemInternal(SystemMessage),
+    SystemPublic(SystemMessage),
     User(UserMessage),
 }
 
+#[derive(Clone, Debug)]
+pub struct SystemMessage {
+    pub content: String,
+    pub sent_at: DateTime<Utc>, // system messages are ours, so they use our clock
+}
+
 #[derive(Clone, Debug)]
 pub struct UserMessage {
     pub username: String,
     pub content: String,
     pub sent_at: DateTime<Utc>,
+    pub local_id: Option<usize>, // only set for messages we sent, see OutboxMessage
+    pub status: DeliveryStatus,
+}
+
+// Delivery of our own messages, messages from others are always Sent
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Pending, // queued in the outbox or waiting for the server to acknowledge it
+    #[default]
+    Sent,
+    Failed,
+}
+
+// A message waiting in a room's outbox to be shouted
+#[derive(Clone, Debug)]
+pub struct OutboxMessage {
+    pub local_id: usize,
+    pub content: String,
+    pub attempts: usize,
+}
+
+impl Message {
+    pub fn sent_at(&self) -> DateTime<Utc> {
+        match self {
+            Self::SystemError(message) => message.sent_at,
+            Self::SystemInternal(message) => message.sent_at,
+            Self::SystemPublic(message) => message.sent_at,
+            Self::User(message) => message.sent_at,
+        }
+    }
+}
+
+impl SystemMessage {
+    pub fn new(content: String) -> Self {
+        Self {
+            content,
+            sent_at: Utc::now(),
+        }
+    }
+}
+
+impl fmt::Display for SystemMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.content)
+    }
 }
 
 impl fmt::Display for Message {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Self::SystemError(message) => write!(f, "{}", message),
             Self::SystemInternal(message) => write!(f, "{}", message),
             Self::SystemPublic(message) => write!(f, "{}", message),
             Self::User(message) => write!(f, "{}: {}", message.username, message.content),
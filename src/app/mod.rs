@@ -1,23 +1,52 @@
+pub mod input;
+pub mod input_history;
 pub mod log;
 pub mod message;
 pub mod room;
 pub mod user;
+use self::input::Input;
+use self::input_history::{InputHistory, InputSearch};
 use self::log::Log;
-use self::message::{Message, UserMessage};
-use crate::app::{room::Room, user::User};
+use self::message::{DeliveryStatus, Message, OutboxMessage, SystemMessage, UserMessage};
+use self::user::UserDetails;
+use crate::app::room::{ChannelStatus, JoinedRoom, Room};
+use crate::app::user::User;
+use crate::bots::{BotEvent, BotRegistry};
+use crate::config::Profile;
+use crate::error::BooError;
+use crate::history::{History, HistoryConfig};
 use crate::names::generate_valid_room_name;
-use crate::socket::request::Request;
-use chrono::Utc;
+use crate::socket::request::{direct_room, is_direct_room, Request, Shout as ShoutRequest};
+use crate::socket::response::Presence;
+use crate::ui::timestamps::TimestampFormat;
+use chrono::{DateTime, Utc};
 use ratatui::widgets::TableState;
 use regex::Regex;
-use std::env;
+use std::collections::HashMap;
 use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
 use url::Url;
 
 /// This module contains the AppState struct used to store the state of the application.
 
-const HEARTBEAT_INTERVAL: Duration = Duration::new(30, 0); // 30 seconds
 const SOCKET_ACTIVITY_DURATION: Duration = Duration::new(0, 500_000_000); // 0.5 seconds
+const MAX_SHOUT_ATTEMPTS: usize = 3;
+const DEFAULT_MAX_INPUT_LINES: u16 = 6;
+
+// Limits of what the server accepts. Message lengths are in graphemes (see message_length), the
+// message limit can be set per profile for a server configured with another one. Room names and
+// usernames are ASCII, so their lengths are the same in bytes
+const DEFAULT_MAX_MESSAGE_LENGTH: usize = 200;
+const MIN_NAME_LENGTH: usize = 3;
+const MAX_NAME_LENGTH: usize = 20;
+
+// Delay before each rejoin attempt of an errored channel, the last one repeats
+const REJOIN_DELAYS: [Duration; 4] = [
+    Duration::new(1, 0),
+    Duration::new(2, 0),
+    Duration::new(5, 0),
+    Duration::new(10, 0),
+];
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub enum Onboarding {
@@ -32,6 +61,7 @@ pub enum Focus {
     #[default]
     Input,
     Rooms,
+    Users,
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -50,98 +80,141 @@ pub enum SocketStatus {
     Disconnected,
 }
 
-// TODO: store users and rooms as HashMap<String, User/Room> to allow for quick adds and removes
 // TODO: nest ui state in a struct
 
 #[derive(Debug)]
 pub struct AppState {
-    pub input: String,
+    pub input: Input,
     pub onboarding: Onboarding,
-    pub room: String,
+    pub room: String, // the active room, i.e. the one shown in the chat pane
     pub socket_status: SocketStatus,
     pub socket_url: Option<String>,
     pub ui_focus_area: Focus,
-    pub ui_input_width: u16,
+    pub ui_input_cursor_x: u16, // position of the cursor within the input area
+    pub ui_input_cursor_y: u16,
+    pub ui_input_max_lines: u16,
     pub ui_messages_area_height: usize,
-    pub ui_messages_line_length: usize,
-    pub ui_messages_scrollbar_position: usize,
     pub ui_right_sidebar_view: RightSidebar,
     pub ui_room_table_state: TableState,
+    pub ui_timestamp_format: TimestampFormat,
+    pub ui_user_table_state: TableState,
     pub user: User,
-    last_heartbeat: Instant,
+    auto_join_rooms: Vec<String>,
+    bots: BotRegistry,
+    held_errors: Vec<Message>, // reported before any room was joined, shown in the first one
+    history: Option<History>,
+    input_history: InputHistory,
+    joined_rooms: HashMap<String, JoinedRoom>,
     logging_enabled: bool,
     logs: Vec<Log>,
-    messages: Vec<Message>,
+    max_message_length: usize,
+    next_local_message_id: usize,
+    pending_paste: Option<String>, // over the length limit, waiting to be split or dropped
+    quit_requested: bool,
     quitting: bool,
     rooms: Vec<Room>,
     showing_help: bool,
+    showing_user_details: bool,
     socket_activity: bool,
+    socket_connect_count: usize,
     socket_last_active: Instant,
     ui_selected_room_index: Option<usize>,
-    users: Vec<User>,
+    ui_selected_user_index: Option<usize>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
-        let room = room_from_env_or_generate();
-        let user = User::new_from_env_or_generate();
+        Self::new(&Profile::default())
+    }
+}
+
+impl AppState {
+    pub fn new(profile: &Profile) -> Self {
+        let room = room_or_generate(profile.rooms.first().cloned());
+        let user = User::new_or_generate(profile.username.clone());
+
+        // rooms of the profile besides the first are joined once onboarding is complete
+        let auto_join_rooms = profile
+            .rooms
+            .iter()
+            .filter(|auto_join_room| **auto_join_room != room)
+            .cloned()
+            .collect();
 
         // Initial input is set to username, to prefill for ConfirmingUsername onboarding step
         let initial_input = user.username.clone();
 
         AppState {
-            input: initial_input,
-            last_heartbeat: Instant::now(),
+            auto_join_rooms,
+            bots: BotRegistry::from_configs(&profile.bots),
+            held_errors: Vec::new(),
+            history: None,
+            input: Input::new(initial_input),
+            input_history: InputHistory::default(),
+            joined_rooms: HashMap::new(),
             logging_enabled: true,
             logs: Vec::new(),
-            messages: Vec::new(),
+            max_message_length: profile
+                .max_message_length
+                .unwrap_or(DEFAULT_MAX_MESSAGE_LENGTH),
+            next_local_message_id: 0,
             onboarding: Onboarding::default(),
+            pending_paste: None,
+            quit_requested: false,
             quitting: false,
             room: room.clone(),
             rooms: Vec::new(),
             showing_help: false,
+            showing_user_details: false,
             socket_activity: false,
+            socket_connect_count: 0,
             socket_last_active: Instant::now(),
             socket_status: SocketStatus::default(),
             socket_url: None,
             ui_focus_area: Focus::default(),
-            ui_input_width: 0,
+            ui_input_cursor_x: 0,
+            ui_input_cursor_y: 0,
+            ui_input_max_lines: profile
+                .max_input_lines
+                .unwrap_or(DEFAULT_MAX_INPUT_LINES)
+                .max(1),
             ui_messages_area_height: 0,
-            ui_messages_line_length: 0,
-            ui_messages_scrollbar_position: 0,
             ui_right_sidebar_view: RightSidebar::default(),
             ui_room_table_state: TableState::default(),
+            ui_timestamp_format: profile.timestamps,
             ui_selected_room_index: None,
+            ui_selected_user_index: None,
+            ui_user_table_state: TableState::default(),
             user: user.clone(),
-            users: Vec::new(),
         }
     }
-}
 
-impl AppState {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn set_input_cursor_position(&mut self, x: u16, y: u16) {
+        self.ui_input_cursor_x = x;
+        self.ui_input_cursor_y = y;
     }
 
-    // heartbeat
-
-    pub fn update_heartbeat_timer(&mut self) -> bool {
-        if self.last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
-            self.last_heartbeat = Instant::now();
-            true
-        } else {
-            false
+    // lines of the input area, it grows with the input up to the configured maximum
+    pub fn get_input_height(&self) -> u16 {
+        if self.is_searching_input() {
+            return 1;
         }
+        let line_count = u16::try_from(self.input.line_count()).unwrap_or(u16::MAX);
+        line_count.min(self.ui_input_max_lines)
     }
 
-    pub fn set_input_width(&mut self, width: u16) {
-        self.ui_input_width = width;
-    }
-
-    // messages scrollbar
+    // messages scrollbar (of the active room)
 
     pub fn get_scrollbar_position(&self) -> usize {
-        self.ui_messages_scrollbar_position
+        self.current_room()
+            .map(|room| room.scrollbar_position)
+            .unwrap_or(0)
+    }
+
+    pub fn get_messages_line_length(&self) -> usize {
+        self.current_room()
+            .map(|room| room.line_length)
+            .unwrap_or(0)
     }
 
     pub fn set_messages_line_length_and_area_height(
@@ -150,25 +223,33 @@ impl AppState {
         area_height: usize,
     ) {
         let scrollbar_was_at_bottom = self.is_messages_scrollbar_at_bottom();
-
-        self.ui_messages_line_length = line_length;
         self.ui_messages_area_height = area_height;
 
         // TODO: add proportional scrollbar update
 
-        if scrollbar_was_at_bottom {
-            self.ui_messages_scrollbar_position = line_length.saturating_sub(area_height);
+        if let Some(room) = self.current_room_mut() {
+            // older messages were prepended, keep showing the same lines
+            if let Some(previous_line_length) = room.scroll_anchor.take() {
+                room.scrollbar_position += line_length.saturating_sub(previous_line_length);
+            } else if scrollbar_was_at_bottom {
+                room.scrollbar_position = line_length.saturating_sub(area_height);
+            }
+            room.line_length = line_length;
         }
     }
 
     fn is_messages_scrollbar_at_bottom(&self) -> bool {
         let bottom_position = self
-            .ui_messages_line_length
+            .get_messages_line_length()
             .saturating_sub(self.ui_messages_area_height);
-        self.ui_messages_scrollbar_position == bottom_position
+        self.get_scrollbar_position() == bottom_position
     }
 
+    // scrolling past the top loads the previous page of history
     pub fn scroll_messages_up(&mut self) {
+        if self.get_scrollbar_position() == 0 {
+            self.load_older_history();
+        }
         self.update_scroll_position(-1)
     }
 
@@ -177,11 +258,12 @@ impl AppState {
     }
 
     fn update_scroll_position(&mut self, delta: isize) {
-        let new_position = (self.ui_messages_scrollbar_position as isize + delta).max(0) as usize;
-        let max_position = self
-            .ui_messages_line_length
-            .saturating_sub(self.ui_messages_area_height);
-        self.ui_messages_scrollbar_position = new_position.min(max_position);
+        let area_height = self.ui_messages_area_height;
+        if let Some(room) = self.current_room_mut() {
+            let new_position = (room.scrollbar_position as isize + delta).max(0) as usize;
+            let max_position = room.line_length.saturating_sub(area_height);
+            room.scrollbar_position = new_position.min(max_position);
+        }
     }
 
     // socket activity
@@ -195,12 +277,25 @@ impl AppState {
         self.socket_last_active = Instant::now();
     }
 
-    pub fn tick_socket_activity(&mut self) {
+    // returns true if the activity indicator changed
+    pub fn tick_socket_activity(&mut self) -> bool {
         if self.socket_activity && self.socket_last_active.elapsed() >= SOCKET_ACTIVITY_DURATION {
             self.socket_activity = false;
+            true
+        } else {
+            false
         }
     }
 
+    // socket status
+
+    // returns true if this connect is a reconnect
+    pub fn set_socket_connected(&mut self) -> bool {
+        self.socket_status = SocketStatus::Connected;
+        self.socket_connect_count += 1;
+        self.socket_connect_count > 1
+    }
+
     // quitting app
 
     pub fn quitting(&self) -> bool {
@@ -208,16 +303,37 @@ impl AppState {
     }
 
     pub fn quit(&mut self) {
+        let pending_count = self.get_pending_message_count();
+        if pending_count > 0 {
+            ::log::warn!("quitting with {pending_count} messages not sent");
+        }
         self.quitting = true;
     }
 
+    // quit, but without a history to keep the outbox in, with messages not sent yet the first
+    // request warns about them and the next one quits
+    pub fn request_quit(&mut self) {
+        let pending_count = self.get_pending_message_count();
+        if pending_count > 0 && self.history.is_none() && !self.quit_requested {
+            self.quit_requested = true;
+            let message = format!(
+                "{} message(s) not sent yet, quit again to drop them",
+                pending_count
+            );
+            self.add_system_internal_message(message);
+            return;
+        }
+        self.quit();
+    }
+
     // socket_url
 
+    // the port is kept unless it is the scheme's default, servers on one host are told apart by it
     pub fn set_socket_url(&mut self, url: Url) {
-        self.socket_url = if let Some(host) = url.host_str() {
-            Some(format!("{}://{}", url.scheme(), host))
-        } else {
-            None
+        self.socket_url = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => Some(format!("{}://{}:{}", url.scheme(), host, port)),
+            (Some(host), None) => Some(format!("{}://{}", url.scheme(), host)),
+            (None, _) => None,
         };
     }
 
@@ -228,7 +344,7 @@ impl AppState {
             Onboarding::ConfirmingUsername => {
                 // advance to confirming room name, set input to initial room name
                 self.onboarding = Onboarding::ConfirmingRoom;
-                self.input = self.room.clone();
+                self.input.set(self.room.clone());
             }
             Onboarding::ConfirmingRoom => {
                 // advance to completed, clear input
@@ -239,53 +355,170 @@ impl AppState {
         };
     }
 
+    // complete onboarding with the configured username and room, the rooms are joined once the
+    // socket is connected
+    pub fn skip_onboarding(&mut self) {
+        self.join_room(self.room.clone());
+        if let Some(joined_room) = self.current_room_mut() {
+            joined_room.rejoin_at = Some(Instant::now());
+        }
+        self.join_auto_join_rooms();
+        self.onboarding = Onboarding::Completed;
+        self.input.clear();
+    }
+
     // input
 
     pub fn get_input(&self) -> String {
-        self.input.clone()
+        self.input.to_string()
     }
 
-    pub fn input_is_valid_command(&self) -> bool {
-        let re = Regex::new(r"^\/[a-zA-Z0-9]{1,10}($| [a-zA-Z0-9\-]{1,30}$)").unwrap();
-        re.is_match(&self.input)
+    // Messages and commands are only held to the length limit while typing, blank messages are
+    // rejected on submit and commands are parsed and validated then, see commands::parse_command
+    pub fn is_valid_next_char_for_input(&self, c: char) -> bool {
+        let new_input = self.input.with_inserted(c);
+        message_length(&new_input) <= self.max_message_length
     }
 
-    pub fn is_valid_next_char_for_input_command(&self, c: char) -> bool {
-        let new_input = format!("{}{}", self.input, c);
-        let re = Regex::new(r"^\/[a-zA-Z0-9]{0,10}(| [a-zA-Z0-9\-]{0,30})$").unwrap();
-        re.is_match(&new_input)
+    // message is not blank and is at most max_message_length graphemes
+    pub fn is_valid_message(&self, message: &str) -> bool {
+        !is_blank(message) && message_length(message) <= self.max_message_length
     }
 
-    // message is not blank and is less than 200 characters
     pub fn input_is_valid_message(&self) -> bool {
-        !self.input_is_blank() && self.input.len() <= 200
-    }
-
-    pub fn is_valid_next_char_for_input_message(&self, c: char) -> bool {
-        let new_message = format!("{}{}", self.input, c);
-        new_message.len() <= 200
+        self.is_valid_message(self.input.as_str())
     }
 
     // room name is alphanumeric and hyphens, between 3 and 20 characters
     pub fn input_is_valid_room_name(&self) -> bool {
-        is_valid_room_or_username(&self.input)
+        is_valid_room_or_username(self.input.as_str())
     }
 
     pub fn is_valid_next_char_for_room_name(&self, c: char) -> bool {
-        is_valid_room_or_username_with(&self.input, c)
+        is_valid_partial_room_or_username(&self.input.with_inserted(c))
     }
 
     // username is alphanumeric and hyphens, between 3 and 20 characters
     pub fn input_is_valid_username(&self) -> bool {
-        is_valid_room_or_username(&self.input)
+        is_valid_room_or_username(self.input.as_str())
     }
 
     pub fn is_valid_next_char_for_username(&self, c: char) -> bool {
-        is_valid_room_or_username_with(&self.input, c)
+        is_valid_partial_room_or_username(&self.input.with_inserted(c))
+    }
+
+    pub fn get_max_message_length(&self) -> usize {
+        self.max_message_length
+    }
+
+    // a paste is validated as a whole, it is inserted entirely or not at all
+    pub fn is_valid_paste_for_input(&self, text: &str) -> bool {
+        let new_input = self.input.with_inserted_str(text);
+        match self.onboarding {
+            Onboarding::Completed => message_length(&new_input) <= self.max_message_length,
+            Onboarding::ConfirmingRoom | Onboarding::ConfirmingUsername => {
+                is_valid_partial_room_or_username(&new_input)
+            }
+        }
+    }
+
+    // pending paste
+
+    pub fn get_pending_paste(&self) -> Option<&str> {
+        self.pending_paste.as_deref()
+    }
+
+    pub fn set_pending_paste(&mut self, text: String) {
+        self.pending_paste = Some(text);
+    }
+
+    pub fn cancel_pending_paste(&mut self) {
+        self.pending_paste = None;
+    }
+
+    // the input with the paste inserted, split into messages within the length limit
+    pub fn get_pending_paste_messages(&self) -> Vec<String> {
+        match &self.pending_paste {
+            Some(text) => {
+                split_message(&self.input.with_inserted_str(text), self.max_message_length)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn send_pending_paste(&mut self) {
+        let messages = self.get_pending_paste_messages();
+        self.pending_paste = None;
+        self.input.clear();
+        for message in messages {
+            self.queue_user_message(message);
+        }
+    }
+
+    // input history
+
+    // submitted messages and commands are kept for recall, and saved with the chat history
+    pub fn add_to_input_history(&mut self, line: String) {
+        if !self.input_history.push(line.clone()) {
+            return;
+        }
+        if let Some(history) = &self.history {
+            if let Err(error) = history.save_input(&line) {
+                ::log::error!("{error}");
+            }
+        }
+    }
+
+    pub fn recall_prev_input(&mut self) {
+        if let Some(line) = self.input_history.recall_prev(self.input.as_str()) {
+            self.input.set(line);
+        }
+    }
+
+    pub fn recall_next_input(&mut self) {
+        if let Some(line) = self.input_history.recall_next() {
+            self.input.set(line);
+        }
+    }
+
+    pub fn get_input_search(&self) -> Option<&InputSearch> {
+        self.input_history.get_search()
+    }
+
+    pub fn get_input_search_match(&self) -> Option<&str> {
+        self.input_history.get_search_match()
+    }
+
+    pub fn is_searching_input(&self) -> bool {
+        self.input_history.is_searching()
     }
 
-    fn input_is_blank(&self) -> bool {
-        is_blank(&self.input)
+    pub fn start_input_search(&mut self) {
+        self.input_history.start_search(self.input.as_str());
+    }
+
+    pub fn search_older_input(&mut self) {
+        self.input_history.search_older();
+    }
+
+    pub fn push_input_search_char(&mut self, c: char) {
+        self.input_history.push_search_char(c);
+    }
+
+    pub fn pop_input_search_char(&mut self) {
+        self.input_history.pop_search_char();
+    }
+
+    pub fn accept_input_search(&mut self) {
+        if let Some(line) = self.input_history.accept_search() {
+            self.input.set(line);
+        }
+    }
+
+    pub fn cancel_input_search(&mut self) {
+        if let Some(line) = self.input_history.cancel_search() {
+            self.input.set(line);
+        }
     }
 
     // rooms
@@ -294,14 +527,25 @@ impl AppState {
         self.room.clone()
     }
 
+    // Rooms from the server, plus any joined rooms the server hasn't told us about yet. Direct
+    // message conversations are not rooms, they are listed with the users instead
     pub fn get_rooms(&self) -> Vec<Room> {
-        self.rooms.clone()
+        let mut rooms = self.rooms.clone();
+        for joined_room in self.joined_rooms.values() {
+            if joined_room.peer.is_none() && !rooms.iter().any(|room| room.name == joined_room.name)
+            {
+                rooms.push(Room {
+                    name: joined_room.name.clone(),
+                    user_count: joined_room.presences.len() as u32,
+                });
+            }
+        }
+        rooms.sort_by_key(|room| room.name.clone());
+        rooms
     }
 
     pub fn set_rooms(&mut self, rooms: Vec<Room>) {
-        let mut new_rooms = rooms.clone();
-        new_rooms.sort_by_key(|room| room.name.clone());
-        self.rooms = new_rooms;
+        self.rooms = rooms;
         self.set_selected_to_current_room()
     }
 
@@ -312,12 +556,248 @@ impl AppState {
             .collect()
     }
 
+    // joined rooms
+
+    pub fn is_joined(&self, room: &str) -> bool {
+        self.joined_rooms.contains_key(room)
+    }
+
+    pub fn get_joined_room_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.joined_rooms.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn get_join_ref(&self, room: &str) -> Option<String> {
+        self.joined_rooms
+            .get(room)
+            .map(|room| room.join_ref.clone())
+    }
+
+    pub fn get_unread_count(&self, room: &str) -> Option<usize> {
+        self.joined_rooms.get(room).map(|room| room.unread_count)
+    }
+
+    // add room to joined rooms (if not already joined) and make it the active room
+    pub fn join_room(&mut self, room: String) {
+        if !self.is_joined(&room) {
+            self.insert_joined_room(JoinedRoom::new(room.clone()));
+        }
+        self.switch_to_room(room);
+    }
+
+    // every joined room starts with the latest page of its history, followed by our messages
+    // still queued from a previous session (or a previous join)
+    fn insert_joined_room(&mut self, mut joined_room: JoinedRoom) {
+        if let Some(history) = &self.history {
+            match history.load_page(&joined_room.history_name(), None) {
+                Ok(page) => joined_room.prepend_history(page),
+                Err(error) => ::log::error!("{error}"),
+            }
+            match history.load_outbox(&joined_room.history_name()) {
+                Ok(outbox) => {
+                    for (outbox_message, written_at) in outbox {
+                        joined_room.add_message(Message::User(UserMessage {
+                            username: self.user.username.clone(),
+                            content: outbox_message.content.clone(),
+                            sent_at: written_at,
+                            local_id: Some(outbox_message.local_id),
+                            status: DeliveryStatus::Pending,
+                        }));
+                        joined_room.outbox.push_back(outbox_message);
+                    }
+                }
+                Err(error) => ::log::error!("{error}"),
+            }
+        }
+        for message in self.held_errors.drain(..) {
+            joined_room.add_message(message);
+        }
+        self.joined_rooms
+            .insert(joined_room.name.clone(), joined_room);
+    }
+
+    // remove room from joined rooms, if it was active switch to the next joined room, preferring
+    // rooms over direct message conversations
+    pub fn leave_room(&mut self, room: &str) {
+        self.joined_rooms.remove(room);
+        if self.room == room {
+            let joined_room_names = self.get_joined_room_names();
+            let next_room = joined_room_names
+                .iter()
+                .find(|name| !is_direct_room(name))
+                .or(joined_room_names.first());
+            if let Some(next_room) = next_room {
+                self.switch_to_room(next_room.clone());
+            }
+        }
+        // the room list got shorter, the selected index may be past its end
+        self.set_selected_to_current_room();
+    }
+
+    // "#room" for rooms, "@username" for direct message conversations
+    pub fn get_room_label(&self, room: &str) -> String {
+        match self
+            .joined_rooms
+            .get(room)
+            .and_then(|room| room.peer.as_ref())
+        {
+            Some(peer) => format!("@{}", peer.username),
+            None => format!("#{}", room),
+        }
+    }
+
+    // direct messages
+
+    // add the direct message conversation with peer to the joined rooms (if not already open),
+    // its channel is joined by the next take_due_rejoin_requests. Returns the conversation's room
+    pub fn open_direct_room(&mut self, peer: User) -> String {
+        let room = direct_room(&self.user.uuid, &peer.uuid);
+        if !self.is_joined(&room) {
+            let mut joined_room = JoinedRoom::new(room.clone());
+            joined_room.peer = Some(peer);
+            joined_room.rejoin_at = Some(Instant::now());
+            self.insert_joined_room(joined_room);
+        }
+        room
+    }
+
+    // None if we have no conversation with the user
+    pub fn get_direct_unread_count(&self, uuid: &str) -> Option<usize> {
+        self.get_unread_count(&direct_room(&self.user.uuid, uuid))
+    }
+
+    // unread messages of all direct conversations, whichever room is active
+    pub fn get_direct_unread_total(&self) -> usize {
+        self.joined_rooms
+            .values()
+            .filter(|room| room.peer.is_some())
+            .map(|room| room.unread_count)
+            .sum()
+    }
+
+    // the other users of our direct conversations, they may share none of our rooms
+    fn get_direct_peers(&self) -> Vec<User> {
+        self.joined_rooms
+            .values()
+            .filter_map(|room| room.peer.clone())
+            .collect()
+    }
+
+    // a user of any of our rooms or direct conversations, other than us
+    pub fn find_user(&self, username: &str) -> Option<User> {
+        self.joined_rooms
+            .values()
+            .flat_map(|room| room.users())
+            .chain(self.get_direct_peers())
+            .find(|user| user.username == username && user.uuid != self.user.uuid)
+    }
+
+    // add the profile's other rooms, they are joined by the next take_due_rejoin_requests
+    pub fn join_auto_join_rooms(&mut self) {
+        let now = Instant::now();
+        for room in std::mem::take(&mut self.auto_join_rooms) {
+            if self.is_joined(&room) || !is_valid_room_or_username(&room) {
+                continue;
+            }
+            let mut joined_room = JoinedRoom::new(room.clone());
+            joined_room.rejoin_at = Some(now);
+            self.insert_joined_room(joined_room);
+            self.add_system_internal_message(format!("joining #{}", room));
+        }
+    }
+
+    pub fn switch_to_room(&mut self, room: String) {
+        if let Some(joined_room) = self.joined_rooms.get_mut(&room) {
+            joined_room.unread_count = 0;
+        }
+        self.room = room;
+        self.set_selected_to_current_room();
+    }
+
+    // channel status
+
+    pub fn is_room_errored(&self, room: &str) -> bool {
+        self.joined_rooms
+            .get(room)
+            .is_some_and(|room| matches!(room.status, ChannelStatus::Errored(_)))
+    }
+
+    pub fn set_room_joined(&mut self, room: &str) {
+        if let Some(joined_room) = self.joined_rooms.get_mut(room) {
+            joined_room.status = ChannelStatus::Joined;
+            joined_room.rejoin_attempts = 0;
+            joined_room.rejoin_at = None;
+        }
+    }
+
+    pub fn set_room_errored(&mut self, room: &str, reason: String) {
+        if let Some(joined_room) = self.joined_rooms.get_mut(room) {
+            joined_room.status = ChannelStatus::Errored(reason);
+        }
+    }
+
+    // schedule a rejoin, backing off on repeated attempts
+    pub fn schedule_rejoin(&mut self, room: &str) {
+        if let Some(joined_room) = self.joined_rooms.get_mut(room) {
+            let index = joined_room.rejoin_attempts.min(REJOIN_DELAYS.len() - 1);
+            joined_room.rejoin_at = Some(Instant::now() + REJOIN_DELAYS[index]);
+            joined_room.rejoin_attempts += 1;
+        }
+    }
+
+    // schedule an immediate rejoin of every joined room, e.g. after the socket reconnected
+    pub fn rejoin_all_rooms(&mut self) {
+        let now = Instant::now();
+        for joined_room in self.joined_rooms.values_mut() {
+            joined_room.status = ChannelStatus::Joining;
+            joined_room.rejoin_attempts = 0;
+            joined_room.rejoin_at = Some(now);
+        }
+    }
+
+    // join requests (with fresh join_refs) for every room whose rejoin is due, once connected
+    pub fn take_due_rejoin_requests(&mut self) -> Vec<Request> {
+        if self.socket_status != SocketStatus::Connected {
+            return Vec::new();
+        }
+
+        let now = Instant::now();
+        let mut requests = Vec::new();
+        for joined_room in self.joined_rooms.values_mut() {
+            if joined_room
+                .rejoin_at
+                .is_some_and(|rejoin_at| rejoin_at <= now)
+            {
+                joined_room.rejoin_at = None;
+                joined_room.status = ChannelStatus::Joining;
+                joined_room.reset_join_ref();
+                requests.push(Request::new_join(
+                    joined_room.name.clone(),
+                    joined_room.join_ref.clone(),
+                    self.user.clone(),
+                ));
+            }
+        }
+        requests
+    }
+
+    fn current_room(&self) -> Option<&JoinedRoom> {
+        self.joined_rooms.get(&self.room)
+    }
+
+    fn current_room_mut(&mut self) -> Option<&mut JoinedRoom> {
+        self.joined_rooms.get_mut(&self.room)
+    }
+
     // UI
 
+    // input, then users, then rooms if the rooms sidebar is shown
     pub fn cycle_focus(&mut self) {
         self.ui_focus_area = match self.ui_focus_area {
-            Focus::Input => Focus::Rooms,
-            Focus::Rooms => Focus::Input,
+            Focus::Input => Focus::Users,
+            Focus::Users if self.ui_right_sidebar_view == RightSidebar::Rooms => Focus::Rooms,
+            Focus::Users | Focus::Rooms => Focus::Input,
         };
     }
 
@@ -333,10 +813,22 @@ impl AppState {
         }
     }
 
+    pub fn showing_user_details(&self) -> bool {
+        self.showing_user_details
+    }
+
+    // details of the selected user, see get_user_details
+    pub fn toggle_show_user_details(&mut self) {
+        self.showing_user_details =
+            !self.showing_user_details && self.get_selected_user().is_some();
+    }
+
     pub fn toggle_right_sidebar(&mut self) {
         match self.ui_right_sidebar_view {
             RightSidebar::Rooms => {
-                self.ui_focus_area = Focus::Input;
+                if self.ui_focus_area == Focus::Rooms {
+                    self.ui_focus_area = Focus::Input;
+                }
                 self.ui_right_sidebar_view = RightSidebar::Logs;
             }
             RightSidebar::Logs => self.ui_right_sidebar_view = RightSidebar::Rooms,
@@ -357,43 +849,87 @@ impl AppState {
     }
 
     pub fn get_selected_room_name(&self) -> Option<String> {
-        match self.ui_selected_room_index {
-            Some(index) => Some(self.get_rooms()[index].name.clone()),
-            None => None,
-        }
+        let index = self.ui_selected_room_index?;
+        self.get_rooms().get(index).map(|room| room.name.clone())
     }
 
     pub fn set_selected_to_current_room(&mut self) {
         self.ui_selected_room_index = self.get_rooms().iter().position(|r| r.name == self.room);
     }
 
+    // with a direct conversation active no room is selected, start from the first (or last) room
     pub fn select_next_room(&mut self) {
-        if let Some(index) = self.get_selected_or_current_room_index() {
-            let next_index = (index + 1) % self.get_rooms().len();
+        let num_rooms = self.get_rooms().len();
+        if num_rooms > 0 {
+            let next_index = match self.get_selected_or_current_room_index() {
+                Some(index) => (index + 1) % num_rooms,
+                None => 0,
+            };
             self.ui_selected_room_index = Some(next_index);
         }
     }
 
     pub fn select_prev_room(&mut self) {
-        if let Some(index) = self.get_selected_or_current_room_index() {
-            let num_rooms = self.get_rooms().len();
-            let prev_index = (index + num_rooms - 1) % num_rooms;
+        let num_rooms = self.get_rooms().len();
+        if num_rooms > 0 {
+            let prev_index = match self.get_selected_or_current_room_index() {
+                Some(index) => (index + num_rooms - 1) % num_rooms,
+                None => num_rooms - 1,
+            };
             self.ui_selected_room_index = Some(prev_index);
         }
     }
 
+    // UI: ui_selected_user_index
+
+    pub fn get_selected_user_index(&self) -> Option<usize> {
+        let num_users = self.get_users().len();
+        self.ui_selected_user_index
+            .filter(|index| *index < num_users)
+    }
+
+    pub fn get_selected_user(&self) -> Option<User> {
+        self.get_selected_user_index()
+            .and_then(|index| self.get_users().get(index).cloned())
+    }
+
+    pub fn select_next_user(&mut self) {
+        let num_users = self.get_users().len();
+        if num_users > 0 {
+            let next_index = match self.get_selected_user_index() {
+                Some(index) => (index + 1) % num_users,
+                None => 0,
+            };
+            self.ui_selected_user_index = Some(next_index);
+        }
+    }
+
+    pub fn select_prev_user(&mut self) {
+        let num_users = self.get_users().len();
+        if num_users > 0 {
+            let prev_index = match self.get_selected_user_index() {
+                Some(index) => (index + num_users - 1) % num_users,
+                None => num_users - 1,
+            };
+            self.ui_selected_user_index = Some(prev_index);
+        }
+    }
+
     // users
 
     pub fn get_username(&self) -> String {
         self.user.username.clone()
     }
 
-    pub fn add_user(&mut self, user: User) {
-        if !self.users.iter().any(|u| u.uuid == user.uuid) {
-            self.users.push(user);
+    // returns true if the user just came online in the room, i.e. from their first device
+    pub fn join_presence(&mut self, room: &str, presence: Presence) -> bool {
+        match self.joined_rooms.get_mut(room) {
+            Some(joined_room) => joined_room.join_presence(presence),
+            None => false,
         }
     }
 
+    // users of the active room
     pub fn get_users(&self) -> Vec<User> {
         self.get_users_sorted()
     }
@@ -405,47 +941,394 @@ impl AppState {
             .collect()
     }
 
-    pub fn remove_user(&mut self, user: User) {
-        self.users.retain(|u| u.uuid != user.uuid);
+    // devices the user is present from in the active room
+    pub fn get_device_count(&self, uuid: &str) -> usize {
+        self.current_room()
+            .map(|room| room.device_count(uuid))
+            .unwrap_or_default()
     }
 
-    pub fn set_users(&mut self, users: Vec<User>) {
-        self.users = users;
+    // returns true if the user went offline in the room, i.e. from their last device
+    pub fn leave_presence(&mut self, room: &str, presence: &Presence) -> bool {
+        match self.joined_rooms.get_mut(room) {
+            Some(joined_room) => joined_room.leave_presence(presence),
+            None => false,
+        }
     }
 
+    pub fn rename_user(&mut self, room: &str, user: &User) {
+        if let Some(joined_room) = self.joined_rooms.get_mut(room) {
+            joined_room.rename_user(user);
+        }
+    }
+
+    pub fn set_presences(&mut self, room: &str, presences: Vec<Presence>) {
+        if let Some(joined_room) = self.joined_rooms.get_mut(room) {
+            joined_room.set_presences(presences);
+        }
+    }
+
+    // the rooms (not direct conversations) we share with the user, and their devices in the active
+    // room
+    pub fn get_user_details(&self, user: &User) -> UserDetails {
+        let mut rooms_in_common: Vec<String> = self
+            .joined_rooms
+            .values()
+            .filter(|room| room.peer.is_none() && room.has_user(&user.uuid))
+            .map(|room| room.name.clone())
+            .collect();
+        rooms_in_common.sort();
+
+        UserDetails {
+            user: user.clone(),
+            rooms_in_common,
+            device_count: self.get_device_count(&user.uuid),
+        }
+    }
+
+    // users of the active room, then the peers of our other direct conversations, so that these
+    // stay listed (with their unread count) and can be selected from any room
     fn get_users_sorted(&self) -> Vec<User> {
-        let mut users = self.users.clone();
+        let mut users = self
+            .current_room()
+            .map(|room| room.users())
+            .unwrap_or_default();
         users.sort_by_key(|user| user.username.clone());
+
+        let mut peers: Vec<User> = self
+            .get_direct_peers()
+            .into_iter()
+            .filter(|peer| !users.iter().any(|user| user.uuid == peer.uuid))
+            .collect();
+        peers.sort_by_key(|peer| peer.username.clone());
+        users.extend(peers);
         users
     }
 
     // messages
 
+    // messages of the active room
     pub fn get_messages(&self) -> Vec<Message> {
-        self.messages.clone()
+        self.current_room()
+            .map(|room| room.messages.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn get_room_messages(&self, room: &str) -> &[Message] {
+        self.joined_rooms
+            .get(room)
+            .map(|room| room.messages.as_slice())
+            .unwrap_or_default()
     }
 
-    pub fn add_user_message(&mut self, user: User, content: String) {
-        self.add_message(Message::User(UserMessage {
-            username: user.username.clone(),
+    // whether any of our own messages, in any room, has the given delivery status
+    pub fn has_messages_with_status(&self, status: DeliveryStatus) -> bool {
+        self.joined_rooms.values().any(|room| {
+            room.messages.iter().any(|message| {
+                matches!(message, Message::User(user_message)
+                    if user_message.local_id.is_some() && user_message.status == status)
+            })
+        })
+    }
+
+    // sent_at is the server's time when the payload has it, so every client shows the same time
+    pub fn add_user_message(
+        &mut self,
+        room: &str,
+        user: User,
+        content: String,
+        sent_at: Option<DateTime<Utc>>,
+    ) {
+        self.add_message(
+            room,
+            Message::User(UserMessage {
+                username: user.username.clone(),
+                content,
+                sent_at: sent_at.unwrap_or_else(Utc::now),
+                local_id: None,
+                status: DeliveryStatus::Sent,
+            }),
+        );
+    }
+
+    // add our message to the active room as pending and queue it in the room's outbox
+    pub fn queue_user_message(&mut self, content: String) {
+        let room = self.room.clone();
+        self.queue_room_message(&room, content);
+    }
+
+    // returns false if the room is not joined, the message is then dropped
+    fn queue_room_message(&mut self, room: &str, content: String) -> bool {
+        if !self.is_joined(room) {
+            return false;
+        }
+
+        let local_id = self.next_local_message_id;
+        self.next_local_message_id += 1;
+        let sent_at = Utc::now();
+
+        self.add_message(
+            room,
+            Message::User(UserMessage {
+                username: self.user.username.clone(),
+                content: content.clone(),
+                sent_at,
+                local_id: Some(local_id),
+                status: DeliveryStatus::Pending,
+            }),
+        );
+
+        if let Some(joined_room) = self.joined_rooms.get_mut(room) {
+            let outbox_message = OutboxMessage {
+                local_id,
+                content,
+                attempts: 0,
+            };
+            if let Some(history) = &self.history {
+                let history_name = joined_room.history_name();
+                if let Err(error) =
+                    history.save_outbox_message(&history_name, &outbox_message, sent_at)
+                {
+                    ::log::error!("{error}");
+                }
+            }
+            joined_room.outbox.push_back(outbox_message);
+        }
+        true
+    }
+
+    // our own messages are kept in the history once the server has them, and leave the stored
+    // outbox once they are sent or failed
+    pub fn set_message_status(&mut self, room: &str, local_id: usize, status: DeliveryStatus) {
+        if let Some(joined_room) = self.joined_rooms.get_mut(room) {
+            joined_room.set_message_status(local_id, status);
+            if status == DeliveryStatus::Sent {
+                if let Some(message) = joined_room.get_message(local_id) {
+                    save_to_history(&self.history, &joined_room.history_name(), message);
+                }
+            }
+        }
+        if status != DeliveryStatus::Pending {
+            if let Some(history) = &self.history {
+                if let Err(error) = history.delete_outbox_message(local_id) {
+                    ::log::error!("{error}");
+                }
+            }
+        }
+    }
+
+    // fail the messages still queued for a room that won't be joined, returns how many
+    pub fn fail_outbox(&mut self, room: &str) -> usize {
+        let Some(joined_room) = self.joined_rooms.get_mut(room) else {
+            return 0;
+        };
+        let local_ids: Vec<usize> = joined_room
+            .outbox
+            .drain(..)
+            .map(|outbox_message| outbox_message.local_id)
+            .collect();
+        for local_id in &local_ids {
+            self.set_message_status(room, *local_id, DeliveryStatus::Failed);
+        }
+        local_ids.len()
+    }
+
+    // messages written but not acknowledged yet, in the outbox or waiting for a reply
+    pub fn get_pending_message_count(&self) -> usize {
+        self.joined_rooms
+            .values()
+            .flat_map(|joined_room| joined_room.messages.iter())
+            .filter(|message| {
+                matches!(message, Message::User(message) if message.status == DeliveryStatus::Pending)
+            })
+            .count()
+    }
+
+    // put a shout that was never sent back in the outbox, returns false once it ran out of attempts
+    pub fn requeue_shout(&mut self, shout: ShoutRequest) -> bool {
+        let attempts = shout.attempts + 1;
+        if attempts >= MAX_SHOUT_ATTEMPTS {
+            return false;
+        }
+
+        match self.joined_rooms.get_mut(&shout.room) {
+            Some(joined_room) => {
+                joined_room.set_message_status(shout.local_id, DeliveryStatus::Pending);
+                joined_room.requeue(OutboxMessage {
+                    local_id: shout.local_id,
+                    content: shout.message,
+                    attempts,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    // drain the outboxes of all rooms whose channel is joined, in the order messages were written
+    pub fn take_outbox_requests(&mut self) -> Vec<Request> {
+        if self.socket_status != SocketStatus::Connected {
+            return Vec::new();
+        }
+
+        let mut requests = Vec::new();
+        for joined_room in self.joined_rooms.values_mut() {
+            if joined_room.status != ChannelStatus::Joined {
+                continue;
+            }
+            while let Some(outbox_message) = joined_room.outbox.pop_front() {
+                let (room, join_ref) = (joined_room.name.clone(), joined_room.join_ref.clone());
+                requests.push(match joined_room.peer {
+                    Some(_) => Request::new_direct(room, join_ref, outbox_message),
+                    None => Request::new_shout(room, join_ref, outbox_message),
+                });
+            }
+        }
+        requests
+    }
+
+    // internal messages are only for us, so they always go to the active room
+    pub fn add_system_internal_message(&mut self, message: String) {
+        let room = self.room.clone();
+        self.add_message(&room, Message::SystemInternal(SystemMessage::new(message)));
+    }
+
+    // errors are only for us too, shown in red in the active room
+    pub fn add_system_error_message(&mut self, message: String) {
+        let room = self.room.clone();
+        self.add_message(&room, Message::SystemError(SystemMessage::new(message)));
+    }
+
+    pub fn add_system_public_message(&mut self, room: &str, message: String) {
+        self.add_message(room, Message::SystemPublic(SystemMessage::new(message)));
+    }
+
+    fn add_message(&mut self, room: &str, message: Message) {
+        // our own messages are saved once sent, see set_message_status
+        let is_own_message = matches!(&message, Message::User(user_message)
+            if user_message.local_id.is_some());
+        if let Some(joined_room) = self.joined_rooms.get(room) {
+            if !is_own_message {
+                save_to_history(&self.history, &joined_room.history_name(), &message);
+            }
+        }
+        self.add_unsaved_message(room, message);
+    }
+
+    // e.g. the messages of dev commands, they are shown but not kept in the history
+    pub fn add_unsaved_user_message(&mut self, room: &str, content: String) {
+        let message = Message::User(UserMessage {
+            username: self.user.username.clone(),
             content,
             sent_at: Utc::now(),
-        }));
+            local_id: None,
+            status: DeliveryStatus::Sent,
+        });
+        self.add_unsaved_message(room, message);
     }
 
-    pub fn add_system_internal_message(&mut self, message: String) {
-        self.add_message(Message::SystemInternal(message.clone()));
-        // self.maybe_scroll_messages_down()
+    // messages to a room we haven't joined are dropped, except errors, e.g. the history failing
+    // to open at startup, which wait for the first joined room
+    fn add_unsaved_message(&mut self, room: &str, message: Message) {
+        let is_active_room = self.room == room;
+        let no_room_joined = self.joined_rooms.is_empty();
+        match self.joined_rooms.get_mut(room) {
+            Some(joined_room) => {
+                joined_room.add_message(message);
+                if !is_active_room {
+                    joined_room.unread_count += 1;
+                }
+            }
+            None if no_room_joined && matches!(message, Message::SystemError(_)) => {
+                self.held_errors.push(message);
+            }
+            None => (),
+        }
+    }
+
+    // history
+
+    // history is optional, if it can't be opened we carry on without it. It is opened with the
+    // chat history disabled too, for the entered lines and the outbox
+    pub fn open_history(&mut self, config: &HistoryConfig) {
+        let Some(socket_url) = &self.socket_url else {
+            return;
+        };
+        let server = socket_url.split("://").last().unwrap_or(socket_url);
+
+        let history = match History::open(server, config) {
+            Ok(history) => history,
+            Err(error) => {
+                self.report_error(error);
+                return;
+            }
+        };
+        match history.load_inputs() {
+            Ok(lines) => self.input_history = InputHistory::new(lines),
+            Err(error) => self.report_error(error),
+        }
+        match history.next_local_id() {
+            Ok(local_id) => {
+                self.next_local_message_id = self.next_local_message_id.max(local_id);
+            }
+            Err(error) => self.report_error(error),
+        }
+        self.history = Some(history);
     }
 
-    pub fn add_system_public_message(&mut self, message: String) {
-        self.add_message(Message::SystemPublic(message.clone()));
-        // self.maybe_scroll_messages_down()
+    fn load_older_history(&mut self) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let Some(joined_room) = self.joined_rooms.get_mut(&self.room) else {
+            return;
+        };
+        if joined_room.history_loaded {
+            return;
+        }
+
+        match history.load_page(&joined_room.history_name(), joined_room.history_cursor) {
+            Ok(page) => joined_room.prepend_history(page),
+            Err(error) => ::log::error!("{error}"),
+        }
+    }
+
+    // bots
+
+    // let the bots react to an event, their replies are queued like our own messages. Bots only
+    // run once onboarding is complete, returns true if any replied
+    pub fn notify_bots(&mut self, event: BotEvent) -> bool {
+        if self.onboarding != Onboarding::Completed {
+            return false;
+        }
+
+        let replies = self.bots.dispatch(&event);
+        let replied = !replies.is_empty();
+        for reply in replies {
+            if !self.is_valid_message(&reply.content) {
+                ::log::warn!(
+                    "bot reply to #{} dropped, it is blank or too long",
+                    reply.room
+                );
+            } else if !self.queue_room_message(&reply.room, reply.content) {
+                ::log::warn!(
+                    "bot reply to #{} dropped, the room is not joined",
+                    reply.room
+                );
+            }
+        }
+        replied
     }
 
-    fn add_message(&mut self, message: Message) {
-        self.messages.push(message);
-        // self.maybe_scroll_messages_down()
+    pub fn tick_bots(&mut self) -> bool {
+        self.notify_bots(BotEvent::Tick(Instant::now()))
+    }
+
+    // errors
+
+    pub fn report_error(&mut self, error: BooError) {
+        ::log::error!("{error}");
+        self.add_system_error_message(error.to_string());
     }
 
     // logs
@@ -466,42 +1349,167 @@ impl AppState {
         Request::new_heartbeat()
     }
 
+    // room requests use the join_ref of the joined room, so rooms must be joined first
+
     pub fn join_request(&mut self) -> Request {
-        Request::new_join(self.room.clone(), self.user.clone())
+        let join_ref = self.get_join_ref(&self.room).unwrap_or_default();
+        Request::new_join(self.room.clone(), join_ref, self.user.clone())
     }
 
-    pub fn join_new_room_request(&mut self, new_room: String) -> Request {
-        Request::new_join(new_room, self.user.clone())
+    pub fn leave_request(&mut self, room: String) -> Request {
+        let join_ref = self.get_join_ref(&room).unwrap_or_default();
+        Request::new_leave(room, join_ref)
     }
 
-    pub fn leave_request(&mut self) -> Request {
-        Request::new_leave(self.room.clone())
+    // a rename is sent on every joined channel, rejoins carry the new username in the join payload
+    pub fn rename_requests(&mut self, old_username: &str) -> Vec<Request> {
+        self.get_joined_room_names()
+            .into_iter()
+            .map(|room| {
+                let join_ref = self.get_join_ref(&room).unwrap_or_default();
+                let username = self.user.username.clone();
+                Request::new_rename(room, join_ref, username, old_username.to_string())
+            })
+            .collect()
     }
 
-    pub fn shout_request(&mut self, message: String) -> Request {
-        Request::new_shout(self.room.clone(), message)
+    pub fn leave_all_requests(&mut self) -> Vec<Request> {
+        self.get_joined_room_names()
+            .into_iter()
+            .map(|room| self.leave_request(room))
+            .collect()
     }
 }
 
 pub fn is_valid_room_or_username(name: &str) -> bool {
-    let re = Regex::new(r"^[a-zA-Z0-9\-]{3,20}$").unwrap();
-    re.is_match(&name)
+    let pattern = format!(r"^[a-zA-Z0-9\-]{{{MIN_NAME_LENGTH},{MAX_NAME_LENGTH}}}$");
+    let re = Regex::new(&pattern).unwrap();
+    re.is_match(name)
+}
+
+// a name being typed, it may still be too short
+pub fn is_valid_partial_room_or_username(name: &str) -> bool {
+    let pattern = format!(r"^[a-zA-Z0-9\-]{{1,{MAX_NAME_LENGTH}}}$");
+    let re = Regex::new(&pattern).unwrap();
+    re.is_match(name)
+}
+
+// Length of a message in graphemes, i.e. characters as they are seen, so that an emoji or an
+// accented letter counts once however many bytes or code points it is made of
+pub fn message_length(message: &str) -> usize {
+    message.graphemes(true).count()
 }
 
-pub fn is_valid_room_or_username_with(name: &str, c: char) -> bool {
-    let re = Regex::new(r"^[a-zA-Z0-9\-]{1,20}$").unwrap();
-    let new_name = format!("{}{}", name, c);
-    re.is_match(&new_name)
+// Split a message over the length limit into several, between lines where possible, otherwise
+// between words, or within a word longer than the limit. Blank parts are dropped, the others
+// trimmed at the end
+pub fn split_message(message: &str, max_length: usize) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = String::new();
+    for line in message.split('\n') {
+        for part in split_line(line, max_length) {
+            let length = message_length(&current) + 1 + message_length(&part);
+            if !current.is_empty() && length > max_length {
+                messages.push(std::mem::take(&mut current));
+            } else if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(&part);
+        }
+    }
+    messages.push(current);
+    messages
+        .into_iter()
+        .filter(|message| !is_blank(message))
+        .map(|message| message.trim_end().to_string())
+        .collect()
+}
+
+fn split_line(line: &str, max_length: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut part_length = 0;
+    for word in line.split_inclusive(char::is_whitespace) {
+        // the whitespace after a word doesn't need to fit, it is dropped at a break
+        if part_length > 0 && part_length + message_length(word.trim_end()) > max_length {
+            parts.push(std::mem::take(&mut part));
+            part_length = 0;
+        }
+        for grapheme in word.graphemes(true) {
+            if part_length >= max_length {
+                if is_blank(grapheme) {
+                    continue;
+                }
+                parts.push(std::mem::take(&mut part));
+                part_length = 0;
+            }
+            part.push_str(grapheme);
+            part_length += 1;
+        }
+    }
+    parts.push(part);
+    parts
 }
 
 pub fn is_blank(s: &str) -> bool {
     s.chars().all(char::is_whitespace)
 }
 
-// Get room name from ROOM env var, otherwise generate a room name
-fn room_from_env_or_generate() -> String {
-    match env::var("ROOM") {
-        Ok(room) => room,
-        Err(_) => generate_valid_room_name(),
+// Get the configured room name, otherwise generate a room name
+fn room_or_generate(room: Option<String>) -> String {
+    match room {
+        Some(room) => room,
+        None => generate_valid_room_name(),
+    }
+}
+
+fn save_to_history(history: &Option<History>, room: &str, message: &Message) {
+    if let Some(history) = history {
+        if let Err(error) = history.save(room, message) {
+            ::log::error!("{error}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_length_counts_graphemes() {
+        assert_eq!(message_length("boo"), 3);
+        assert_eq!(message_length("e\u{301}te\u{301}"), 3);
+        assert_eq!(message_length("🇫🇷👻"), 2);
+    }
+
+    #[test]
+    fn message_at_limit_is_not_split() {
+        let message = "e\u{301}".repeat(5);
+        assert_eq!(split_message(&message, 5), vec![message]);
+    }
+
+    #[test]
+    fn message_over_limit_is_split_between_graphemes() {
+        let message = "🇫🇷".repeat(6);
+        assert_eq!(
+            split_message(&message, 5),
+            vec!["🇫🇷".repeat(5), "🇫🇷".to_string()]
+        );
+    }
+
+    #[test]
+    fn message_is_split_between_lines() {
+        assert_eq!(split_message("boo\nboo\n\nboo", 7), vec!["boo\nboo", "boo"]);
+    }
+
+    #[test]
+    fn message_is_split_between_words() {
+        // the space at the break doesn't count towards the limit
+        assert_eq!(split_message("boo boo boo", 7), vec!["boo boo", "boo"]);
+    }
+
+    #[test]
+    fn word_longer_than_limit_is_split_within() {
+        assert_eq!(split_line("boooo", 2), vec!["bo", "oo", "o"]);
     }
 }
@@ -1,7 +1,248 @@
+use crate::app::message::{DeliveryStatus, Message, OutboxMessage};
+use crate::app::user::User;
+use crate::history::HistoryPage;
+use crate::names::generate_uuid;
+use crate::socket::response::Presence;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct Room {
     pub name: String,
     pub user_count: u32,
 }
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ChannelStatus {
+    #[default]
+    Joining,
+    Joined,
+    Errored(String), // reason shown to the user
+}
+
+// A room we are currently joined to, with its own history, users and scroll state
+#[derive(Clone, Default, Debug)]
+pub struct JoinedRoom {
+    pub name: String,
+    pub join_ref: String, // sent with every request on this room's channel
+    pub status: ChannelStatus,
+    pub rejoin_at: Option<Instant>,
+    pub rejoin_attempts: usize,
+    pub messages: Vec<Message>,
+    pub outbox: VecDeque<OutboxMessage>,
+    pub presences: HashMap<String, Vec<User>>, // metas of each user present, by presence key
+    pub unread_count: usize,
+    pub scrollbar_position: usize,
+    pub line_length: usize,
+    pub peer: Option<User>, // the other user of a direct message conversation
+    pub history_cursor: Option<i64>, // where the next (older) page of history is loaded from
+    pub history_loaded: bool, // true once there is no older history left to load
+    pub scroll_anchor: Option<usize>, // line length before older messages were prepended
+}
+
+impl JoinedRoom {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            join_ref: generate_uuid(),
+            ..Default::default()
+        }
+    }
+
+    // a fresh join_ref makes any late messages from the previous join stale
+    pub fn reset_join_ref(&mut self) {
+        self.join_ref = generate_uuid();
+    }
+
+    pub fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    // Name of the room in the chat history. A direct conversation is kept under the other user's
+    // uuid rather than their username, which /nick changes and someone else may take, e.g. "@0a1b…"
+    pub fn history_name(&self) -> String {
+        match &self.peer {
+            Some(peer) => format!("@{}", peer.uuid),
+            None => self.name.clone(),
+        }
+    }
+
+    // older messages go before the ones we have, keeping the scroll position on the same message
+    pub fn prepend_history(&mut self, page: HistoryPage) {
+        if page.messages.is_empty() {
+            self.history_loaded = true;
+            return;
+        }
+        self.history_cursor = page.cursor;
+        if !self.messages.is_empty() {
+            self.scroll_anchor = Some(self.line_length);
+        }
+        self.messages.splice(0..0, page.messages);
+    }
+
+    pub fn get_message(&self, local_id: usize) -> Option<&Message> {
+        self.messages.iter().rev().find(|message| {
+            matches!(message, Message::User(user_message) if user_message.local_id == Some(local_id))
+        })
+    }
+
+    pub fn set_message_status(&mut self, local_id: usize, status: DeliveryStatus) {
+        for message in self.messages.iter_mut().rev() {
+            if let Message::User(user_message) = message {
+                if user_message.local_id == Some(local_id) {
+                    user_message.status = status;
+                    return;
+                }
+            }
+        }
+    }
+
+    // put a message back in the outbox, keeping the outbox in the order messages were written
+    pub fn requeue(&mut self, outbox_message: OutboxMessage) {
+        let index = self
+            .outbox
+            .iter()
+            .position(|queued| queued.local_id > outbox_message.local_id)
+            .unwrap_or(self.outbox.len());
+        self.outbox.insert(index, outbox_message);
+    }
+
+    // users present in the room, as described by their first meta
+    pub fn users(&self) -> Vec<User> {
+        self.presences
+            .values()
+            .filter_map(|metas| metas.first().cloned())
+            .collect()
+    }
+
+    pub fn has_user(&self, uuid: &str) -> bool {
+        self.device_count(uuid) > 0
+    }
+
+    // number of metas of the user, i.e. the devices they are present from
+    pub fn device_count(&self, uuid: &str) -> usize {
+        self.presences
+            .values()
+            .flatten()
+            .filter(|meta| meta.uuid == uuid)
+            .count()
+    }
+
+    // Phoenix Presence merge: a diff only carries the metas which joined, they add to the metas
+    // we already have (unless already known by phx_ref). Returns true if the user wasn't present
+    pub fn join_presence(&mut self, presence: Presence) -> bool {
+        let metas = self.presences.entry(presence.key).or_default();
+        let was_present = !metas.is_empty();
+        for meta in presence.metas {
+            if meta.phx_ref.is_none() || !metas.iter().any(|m| m.phx_ref == meta.phx_ref) {
+                metas.push(meta);
+            }
+        }
+        !was_present
+    }
+
+    // Phoenix Presence merge: only the metas which left are removed, by phx_ref, the user is gone
+    // once their last meta left. Returns true if the user is no longer present
+    pub fn leave_presence(&mut self, presence: &Presence) -> bool {
+        let Some(metas) = self.presences.get_mut(&presence.key) else {
+            return false;
+        };
+        metas.retain(|meta| {
+            !presence
+                .metas
+                .iter()
+                .any(|left| left.phx_ref.is_some() && left.phx_ref == meta.phx_ref)
+        });
+
+        // metas without a phx_ref can't be told apart, so they leave all at once
+        let left_without_ref = presence.metas.iter().any(|left| left.phx_ref.is_none());
+        if metas.is_empty() || left_without_ref {
+            self.presences.remove(&presence.key);
+            return true;
+        }
+        false
+    }
+
+    pub fn set_presences(&mut self, presences: Vec<Presence>) {
+        self.presences = presences
+            .into_iter()
+            .map(|presence| (presence.key, presence.metas))
+            .collect();
+    }
+
+    pub fn rename_user(&mut self, user: &User) {
+        for meta in self.presences.values_mut().flatten() {
+            if meta.uuid == user.uuid {
+                meta.username = user.username.clone();
+            }
+        }
+        if let Some(peer) = self.peer.as_mut().filter(|peer| peer.uuid == user.uuid) {
+            peer.username = user.username.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(uuid: &str, phx_ref: &str) -> User {
+        User {
+            uuid: uuid.to_string(),
+            username: format!("user-{uuid}"),
+            online_at: 0,
+            phx_ref: Some(phx_ref.to_string()),
+        }
+    }
+
+    fn presence(uuid: &str, phx_refs: &[&str]) -> Presence {
+        Presence {
+            key: uuid.to_string(),
+            metas: phx_refs.iter().map(|phx_ref| meta(uuid, phx_ref)).collect(),
+        }
+    }
+
+    #[test]
+    fn user_joins_with_first_device_and_leaves_with_last() {
+        let mut room = JoinedRoom::new("haunted".to_string());
+        assert!(room.join_presence(presence("a", &["ref-1"])));
+        assert!(!room.join_presence(presence("a", &["ref-2"])));
+        assert_eq!(room.device_count("a"), 2);
+
+        assert!(!room.leave_presence(&presence("a", &["ref-1"])));
+        assert_eq!(room.device_count("a"), 1);
+        assert!(room.has_user("a"));
+
+        assert!(room.leave_presence(&presence("a", &["ref-2"])));
+        assert!(!room.has_user("a"));
+    }
+
+    #[test]
+    fn known_device_is_not_added_twice() {
+        let mut room = JoinedRoom::new("haunted".to_string());
+        room.join_presence(presence("a", &["ref-1"]));
+        room.join_presence(presence("a", &["ref-1"]));
+        assert_eq!(room.device_count("a"), 1);
+    }
+
+    #[test]
+    fn meta_update_keeps_user_present() {
+        // a device updating its meta is a join of the new meta and a leave of the old one
+        let mut room = JoinedRoom::new("haunted".to_string());
+        room.join_presence(presence("a", &["ref-1"]));
+
+        assert!(!room.join_presence(presence("a", &["ref-2"])));
+        assert!(!room.leave_presence(&presence("a", &["ref-1"])));
+        assert_eq!(room.device_count("a"), 1);
+        assert_eq!(room.presences["a"][0].phx_ref.as_deref(), Some("ref-2"));
+    }
+
+    #[test]
+    fn leave_of_unknown_user_is_ignored() {
+        let mut room = JoinedRoom::new("haunted".to_string());
+        room.join_presence(presence("a", &["ref-1"]));
+        assert!(!room.leave_presence(&presence("b", &["ref-2"])));
+        assert_eq!(room.users().len(), 1);
+    }
+}
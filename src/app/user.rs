@@ -1,12 +1,15 @@
 use crate::names::{generate_uuid, generate_valid_username};
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
-use std::env;
 
 #[derive(Default, Clone, Serialize, Deserialize, Debug)]
 pub struct User {
     pub uuid: String,
     pub username: String,
     pub online_at: u64,
+    // set by the server on presence metas, one per device, never sent
+    #[serde(default, skip_serializing)]
+    pub phx_ref: Option<String>,
 }
 
 impl User {
@@ -18,11 +21,11 @@ impl User {
         }
     }
 
-    // Create a new user from NAME env var otherwise generate a guest username
-    pub fn new_from_env_or_generate() -> Self {
-        match env::var("NAME") {
-            Ok(username) => Self::new(username),
-            Err(_) => Self::new(generate_valid_username()),
+    // Create a new user from the configured username otherwise generate a guest username
+    pub fn new_or_generate(username: Option<String>) -> Self {
+        match username {
+            Some(username) => Self::new(username),
+            None => Self::new(generate_valid_username()),
         }
     }
 
@@ -31,4 +34,18 @@ impl User {
         let uuid_bit = self.uuid[0..4].to_string();
         format!("{}#{}", self.username, uuid_bit)
     }
+
+    // online_at is set by the server when the user joins, in seconds since the epoch
+    pub fn online_since(&self) -> Option<DateTime<Local>> {
+        let online_at = i64::try_from(self.online_at).ok()?;
+        DateTime::from_timestamp(online_at, 0).map(|online_at| online_at.with_timezone(&Local))
+    }
+}
+
+// What we know about another user, shown in the user details popup
+#[derive(Clone, Debug)]
+pub struct UserDetails {
+    pub user: User,
+    pub rooms_in_common: Vec<String>,
+    pub device_count: usize, // in the active room
 }
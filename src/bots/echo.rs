@@ -0,0 +1,41 @@
+use super::{Bot, BotReply};
+use crate::app::user::User;
+
+/// Replies to `!echo <text>` with `<text>`, in its rooms or in every room if none are configured.
+
+const TRIGGER: &str = "!echo ";
+
+pub struct EchoBot {
+    rooms: Vec<String>,
+}
+
+impl EchoBot {
+    pub fn new(rooms: Vec<String>) -> Self {
+        Self { rooms }
+    }
+
+    fn is_listening_in(&self, room: &str) -> bool {
+        self.rooms.is_empty() || self.rooms.iter().any(|r| r == room)
+    }
+}
+
+impl Bot for EchoBot {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn on_message(&mut self, room: &str, _user: &User, content: &str) -> Vec<BotReply> {
+        let Some(text) = content.strip_prefix(TRIGGER) else {
+            return Vec::new();
+        };
+
+        if !self.is_listening_in(room) || text.trim().is_empty() {
+            return Vec::new();
+        }
+
+        vec![BotReply {
+            room: room.to_string(),
+            content: text.to_string(),
+        }]
+    }
+}
@@ -0,0 +1,151 @@
+mod echo;
+mod reminder;
+use self::echo::EchoBot;
+use self::reminder::ReminderBot;
+use crate::app::room::Room;
+use crate::app::user::User;
+use serde::Deserialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// This module contains the `Bot` trait and the `BotRegistry` of bots configured in the profile.
+/// Bots run inside the client once onboarding is complete. They are told about messages, joins,
+/// leaves and room updates of the rooms we are in, plus a regular tick, and can reply with
+/// messages which are queued and shouted exactly like messages we type ourselves.
+///
+/// ```toml
+/// [[profiles.work.bots]]
+/// kind = "echo"
+/// rooms = ["lobby"]   # all rooms if empty
+///
+/// [[profiles.work.bots]]
+/// kind = "reminder"
+/// room = "team"
+/// message = "stand-up in 5 minutes"
+/// every_minutes = 60
+/// ```
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum BotConfig {
+    Echo {
+        #[serde(default)]
+        rooms: Vec<String>,
+    },
+    Reminder {
+        room: String,
+        message: String,
+        every_minutes: u64,
+    },
+}
+
+// A message a bot wants to send
+#[derive(Clone, Debug)]
+pub struct BotReply {
+    pub room: String,
+    pub content: String,
+}
+
+pub enum BotEvent<'a> {
+    Message {
+        room: &'a str,
+        user: &'a User,
+        content: &'a str,
+    },
+    Join {
+        room: &'a str,
+        user: &'a User,
+    },
+    Leave {
+        room: &'a str,
+        user: &'a User,
+    },
+    RoomsUpdate(&'a [Room]),
+    Tick(Instant),
+}
+
+// Every hook is optional, a bot implements the ones it cares about. Our own messages, joins and
+// leaves are not passed to bots.
+pub trait Bot {
+    fn name(&self) -> &'static str;
+
+    fn on_message(&mut self, _room: &str, _user: &User, _content: &str) -> Vec<BotReply> {
+        Vec::new()
+    }
+
+    fn on_join(&mut self, _room: &str, _user: &User) -> Vec<BotReply> {
+        Vec::new()
+    }
+
+    fn on_leave(&mut self, _room: &str, _user: &User) -> Vec<BotReply> {
+        Vec::new()
+    }
+
+    fn on_rooms_update(&mut self, _rooms: &[Room]) -> Vec<BotReply> {
+        Vec::new()
+    }
+
+    fn on_tick(&mut self, _now: Instant) -> Vec<BotReply> {
+        Vec::new()
+    }
+}
+
+#[derive(Default)]
+pub struct BotRegistry {
+    bots: Vec<Box<dyn Bot>>,
+}
+
+impl BotRegistry {
+    pub fn from_configs(configs: &[BotConfig]) -> Self {
+        let bots = configs
+            .iter()
+            .map(|config| -> Box<dyn Bot> {
+                match config {
+                    BotConfig::Echo { rooms } => Box::new(EchoBot::new(rooms.clone())),
+                    BotConfig::Reminder {
+                        room,
+                        message,
+                        every_minutes,
+                    } => Box::new(ReminderBot::new(
+                        room.clone(),
+                        message.clone(),
+                        Duration::from_secs(60 * (*every_minutes).max(1)),
+                    )),
+                }
+            })
+            .collect();
+
+        Self { bots }
+    }
+
+    pub fn dispatch(&mut self, event: &BotEvent) -> Vec<BotReply> {
+        self.bots
+            .iter_mut()
+            .flat_map(|bot| {
+                let replies = match *event {
+                    BotEvent::Message {
+                        room,
+                        user,
+                        content,
+                    } => bot.on_message(room, user, content),
+                    BotEvent::Join { room, user } => bot.on_join(room, user),
+                    BotEvent::Leave { room, user } => bot.on_leave(room, user),
+                    BotEvent::RoomsUpdate(rooms) => bot.on_rooms_update(rooms),
+                    BotEvent::Tick(now) => bot.on_tick(now),
+                };
+                if !replies.is_empty() {
+                    log::debug!("bot {} replied {:?}", bot.name(), replies);
+                }
+                replies
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for BotRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.bots.iter().map(|bot| bot.name()))
+            .finish()
+    }
+}
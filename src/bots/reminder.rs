@@ -0,0 +1,44 @@
+use super::{Bot, BotReply};
+use std::time::{Duration, Instant};
+
+/// Posts the same message to a room at a fixed interval, the first one an interval after startup.
+
+pub struct ReminderBot {
+    room: String,
+    message: String,
+    every: Duration,
+    next_at: Instant,
+}
+
+impl ReminderBot {
+    pub fn new(room: String, message: String, every: Duration) -> Self {
+        Self {
+            room,
+            message,
+            every,
+            next_at: Instant::now() + every,
+        }
+    }
+}
+
+impl Bot for ReminderBot {
+    fn name(&self) -> &'static str {
+        "reminder"
+    }
+
+    fn on_tick(&mut self, now: Instant) -> Vec<BotReply> {
+        if now < self.next_at {
+            return Vec::new();
+        }
+
+        // skip reminders missed while e.g. the machine was asleep, rather than posting them all
+        while self.next_at <= now {
+            self.next_at += self.every;
+        }
+
+        vec![BotReply {
+            room: self.room.clone(),
+            content: self.message.clone(),
+        }]
+    }
+}
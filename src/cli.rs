@@ -0,0 +1,144 @@
+use crate::app::is_valid_room_or_username;
+use crate::config::Profile;
+use crate::error::{BooError, BooResult};
+use clap::{Parser, Subcommand, ValueEnum};
+
+/// This module contains the command line arguments, they take precedence over the env vars and the
+/// selected config profile.
+///
+/// Without a subcommand the TUI is started. The subcommands connect, do their one thing and exit
+/// with one of the exit codes below, so they can be scripted against.
+
+pub const EXIT_CONNECT_FAILED: u8 = 2;
+pub const EXIT_REQUEST_FAILED: u8 = 3;
+pub const EXIT_TIMED_OUT: u8 = 4;
+
+const LOG_LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "A terminal chat client",
+    after_help = "Exit codes: 0 success, 1 invalid arguments or config, 2 could not connect, \
+                  3 request rejected by the server, 4 timed out"
+)]
+pub struct Cli {
+    /// Profile from the config file to use, defaults to its default_profile
+    #[arg(long, short, global = true)]
+    pub profile: Option<String>,
+
+    /// Server URL, e.g. wss://chat.haunted.host
+    #[arg(long, global = true)]
+    pub url: Option<String>,
+
+    /// Username
+    #[arg(long, global = true)]
+    pub name: Option<String>,
+
+    /// Room to join
+    #[arg(long, global = true)]
+    pub room: Option<String>,
+
+    /// Log level of logs/app.log
+    #[arg(long, global = true, value_parser = LOG_LEVELS)]
+    pub log_level: Option<String>,
+
+    /// Skip confirming the username and room, both must be given (here, in env vars or profile)
+    #[arg(long)]
+    pub no_onboarding: bool,
+
+    /// Run without the TUI: send lines read from stdin to --room, print incoming messages
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Output format of --headless
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain, requires = "headless")]
+    pub output: OutputFormat,
+
+    /// Keep printing incoming messages after stdin is closed, for --headless
+    #[arg(long, requires = "headless")]
+    pub follow: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum OutputFormat {
+    Plain, // "#room username: message"
+    Json,  // one JSON object per line
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Send a message to --room and exit once the server acknowledged it
+    Send {
+        /// Message to send
+        message: String,
+    },
+    /// Print the rooms and their user counts, joins --room (or a generated room) briefly to
+    /// receive them
+    Rooms,
+}
+
+impl Cli {
+    // clap exits with 2 on usage errors, which is EXIT_CONNECT_FAILED here, so they exit with 1
+    // like the other invalid arguments. --help and --version still exit with 0
+    pub fn parse_or_exit() -> Self {
+        Self::try_parse().unwrap_or_else(|e| {
+            let _ = e.print();
+            let exit_code = if e.exit_code() == 0 { 0 } else { 1 };
+            std::process::exit(exit_code)
+        })
+    }
+
+    pub fn apply_overrides(&self, profile: &mut Profile) {
+        if let Some(url) = &self.url {
+            profile.url = Some(url.clone());
+        }
+        if let Some(name) = &self.name {
+            profile.username = Some(name.clone());
+        }
+        if let Some(room) = &self.room {
+            profile.set_room(room.clone());
+        }
+        if let Some(log_level) = &self.log_level {
+            profile.log_level = Some(log_level.clone());
+        }
+    }
+
+    // Onboarding is skipped for --no-onboarding, --headless and subcommands, which need a valid
+    // username and room up front
+    pub fn skips_onboarding(&self) -> bool {
+        self.no_onboarding || self.headless || self.command.is_some()
+    }
+}
+
+// Check the username and room configured for skipping onboarding. The room is required except
+// for `boo rooms`, which joins a generated room when there is none. The username is only
+// required for --no-onboarding, headless mode and subcommands make one up
+pub fn validate_onboarding_profile(cli: &Cli, profile: &Profile) -> BooResult<()> {
+    match profile.rooms.first() {
+        Some(room) if !is_valid_room_or_username(room) => {
+            return Err(BooError::InvalidArgument(format!(
+                "invalid room name {room}"
+            )));
+        }
+        None if !matches!(cli.command, Some(Command::Rooms)) => {
+            return Err(BooError::InvalidArgument(
+                "a room is required, use --room".to_string(),
+            ));
+        }
+        _ => (),
+    }
+
+    match &profile.username {
+        Some(username) if !is_valid_room_or_username(username) => Err(BooError::InvalidArgument(
+            format!("invalid username {username}"),
+        )),
+        None if cli.no_onboarding => Err(BooError::InvalidArgument(
+            "--no-onboarding needs a username, use --name".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
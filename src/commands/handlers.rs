@@ -0,0 +1,156 @@
+use crate::app::AppState;
+use crate::logging::set_log_prefix;
+use crate::socket::request::is_direct_room;
+use crate::socket::{client, send_request};
+use log::debug;
+use lorem_rustum::LoremRustum;
+
+/// Handlers of the commands in `COMMANDS`, their arguments have already been validated against
+/// the command's argument specs.
+
+pub fn help(app: &mut AppState, _handle: &ezsockets::Client<client::Client>, _args: Vec<String>) {
+    app.toggle_show_help();
+}
+
+pub fn join(app: &mut AppState, handle: &ezsockets::Client<client::Client>, args: Vec<String>) {
+    // the last room given ends up as the current room
+    for new_room in args {
+        if new_room != app.room {
+            join_or_switch_to_room(app, handle, new_room);
+        }
+    }
+}
+
+pub fn leave(app: &mut AppState, handle: &ezsockets::Client<client::Client>, args: Vec<String>) {
+    let room = args.into_iter().next().unwrap_or_else(|| app.get_room());
+
+    if !app.is_joined(&room) {
+        let message = format!("can't leave #{}, you haven't joined it", room);
+        app.add_system_error_message(message);
+        return;
+    }
+
+    // we always stay joined to at least one room, direct conversations don't count
+    let joined_room_count = app
+        .get_joined_room_names()
+        .iter()
+        .filter(|name| !is_direct_room(name))
+        .count();
+    if !is_direct_room(&room) && joined_room_count <= 1 {
+        let message = format!("can't leave #{}, it is your only room", room);
+        app.add_system_error_message(message);
+        return;
+    }
+
+    let label = app.get_room_label(&room);
+    let leave_request = app.leave_request(room.clone());
+    debug!("sending leave request={:?}", leave_request);
+    app.set_socket_activity();
+    match send_request(handle, leave_request) {
+        Ok(_) => {
+            app.leave_room(&room);
+            let message = format!("left {}", label);
+            app.add_system_internal_message(message);
+        }
+        Err(error) => app.report_error(error),
+    }
+}
+
+pub fn lorem(app: &mut AppState, _handle: &ezsockets::Client<client::Client>, args: Vec<String>) {
+    let num_lines = args
+        .first()
+        .and_then(|count| count.parse::<usize>().ok())
+        .unwrap_or(1);
+
+    let room = app.get_room();
+    for _ in 0..num_lines {
+        let message = LoremRustum::new(28).to_string();
+        app.add_unsaved_user_message(&room, message);
+    }
+}
+
+pub fn msg(app: &mut AppState, _handle: &ezsockets::Client<client::Client>, args: Vec<String>) {
+    let mut args = args.into_iter();
+    let Some(username) = args.next() else {
+        return;
+    };
+    let username = username.trim_start_matches('@');
+    let message = args.collect::<Vec<String>>().join(" ");
+
+    // users are only known through the presence of the rooms we share with them
+    let Some(peer) = app.find_user(username) else {
+        let message = format!("@{} is not in any of your rooms", username);
+        app.add_system_error_message(message);
+        return;
+    };
+
+    let room = app.open_direct_room(peer);
+    app.switch_to_room(room);
+
+    // without a message this only opens the conversation
+    if message.is_empty() {
+        return;
+    }
+    if !app.is_valid_message(&message) {
+        app.add_system_error_message("message is too long, not sent".to_string());
+        return;
+    }
+    app.queue_user_message(message);
+}
+
+pub fn nick(app: &mut AppState, handle: &ezsockets::Client<client::Client>, args: Vec<String>) {
+    let Some(new_username) = args.into_iter().next() else {
+        return;
+    };
+    let new_username = new_username.trim_start_matches('@').to_string();
+
+    if new_username == app.user.username {
+        let message = format!("you are already known as @{}", new_username);
+        app.add_system_internal_message(message);
+        return;
+    }
+
+    // the other members (and we) see the rename once the server broadcasts it, if it is rejected
+    // we go back to the old username
+    let old_username = std::mem::replace(&mut app.user.username, new_username);
+    set_log_prefix(app.user.display_name());
+
+    app.set_socket_activity();
+    for rename_request in app.rename_requests(&old_username) {
+        debug!("sending rename request={:?}", rename_request);
+        if let Err(error) = send_request(handle, rename_request) {
+            app.report_error(error);
+        }
+    }
+}
+
+pub fn quit(app: &mut AppState, _handle: &ezsockets::Client<client::Client>, _args: Vec<String>) {
+    app.request_quit();
+}
+
+// switch to room if already joined, otherwise join it alongside our other rooms
+pub fn join_or_switch_to_room(
+    app: &mut AppState,
+    handle: &ezsockets::Client<client::Client>,
+    new_room: String,
+) {
+    if app.is_joined(&new_room) {
+        app.switch_to_room(new_room);
+        return;
+    }
+
+    app.join_room(new_room.clone());
+    let join_request = app.join_request();
+    debug!("sending join request={:?}", join_request);
+    app.set_socket_activity();
+    match send_request(handle, join_request) {
+        Ok(_) => {
+            let message = format!("joining #{}", new_room);
+            app.add_system_internal_message(message);
+        }
+        Err(error) => {
+            app.leave_room(&new_room);
+            app.report_error(error);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+mod handlers;
+pub use self::handlers::join_or_switch_to_room;
+use crate::app::{is_valid_room_or_username, AppState};
+use crate::socket::client;
+use std::fmt;
+
+/// This module contains the registry of slash commands. Each command declares its name, aliases,
+/// arguments, help string and handler, and everything else is derived from `COMMANDS`: parsing
+/// and validating the input, the help screen and tab completion.
+///
+/// Arguments are separated by whitespace, an argument containing whitespace can be quoted with
+/// double or single quotes, e.g. `/nick "casper"`.
+
+pub type CommandHandler = fn(&mut AppState, &ezsockets::Client<client::Client>, Vec<String>);
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub args: &'static [ArgSpec],
+    pub help: &'static str,
+    pub hidden: bool, // not shown in help or completed, e.g. commands for development
+    pub handler: CommandHandler,
+}
+
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+    pub variadic: bool, // only the last argument can repeat
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArgKind {
+    Number,
+    Room,
+    Text,
+    Username, // may be prefixed with "@"
+}
+
+pub static COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "help",
+        aliases: &["?"],
+        args: &[],
+        help: "Show this help message",
+        hidden: false,
+        handler: handlers::help,
+    },
+    CommandSpec {
+        name: "join",
+        aliases: &["j"],
+        args: &[ArgSpec {
+            name: "room",
+            kind: ArgKind::Room,
+            required: true,
+            variadic: true,
+        }],
+        help: "Join rooms, or switch to a room already joined",
+        hidden: false,
+        handler: handlers::join,
+    },
+    CommandSpec {
+        name: "leave",
+        aliases: &[],
+        args: &[ArgSpec {
+            name: "room",
+            kind: ArgKind::Room,
+            required: false,
+            variadic: false,
+        }],
+        help: "Leave a room, the current room by default",
+        hidden: false,
+        handler: handlers::leave,
+    },
+    CommandSpec {
+        name: "lorem",
+        aliases: &[],
+        args: &[ArgSpec {
+            name: "count",
+            kind: ArgKind::Number,
+            required: false,
+            variadic: false,
+        }],
+        help: "Add lorem ipsum messages",
+        hidden: true,
+        handler: handlers::lorem,
+    },
+    CommandSpec {
+        name: "msg",
+        aliases: &["dm"],
+        args: &[
+            ArgSpec {
+                name: "@user",
+                kind: ArgKind::Username,
+                required: true,
+                variadic: false,
+            },
+            ArgSpec {
+                name: "message",
+                kind: ArgKind::Text,
+                required: false,
+                variadic: true,
+            },
+        ],
+        help: "Open a direct conversation with a user, and send them a message",
+        hidden: false,
+        handler: handlers::msg,
+    },
+    CommandSpec {
+        name: "nick",
+        aliases: &["username"],
+        args: &[ArgSpec {
+            name: "username",
+            kind: ArgKind::Username,
+            required: true,
+            variadic: false,
+        }],
+        help: "Change your username in every room",
+        hidden: false,
+        handler: handlers::nick,
+    },
+    CommandSpec {
+        name: "quit",
+        aliases: &["q"],
+        args: &[],
+        help: "Quit the application",
+        hidden: false,
+        handler: handlers::quit,
+    },
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+    Empty,
+    UnterminatedQuote,
+    Unknown(String),
+    MissingArgument {
+        command: String,
+        arg: String,
+    },
+    TooManyArguments {
+        command: String,
+    },
+    InvalidArgument {
+        command: String,
+        arg: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "missing command, see /help"),
+            Self::UnterminatedQuote => write!(f, "unterminated quote"),
+            Self::Unknown(name) => write!(f, "unknown command /{name}, see /help"),
+            Self::MissingArgument { command, arg } => {
+                write!(f, "/{command} is missing <{arg}>")
+            }
+            Self::TooManyArguments { command } => {
+                write!(f, "too many arguments for /{command}")
+            }
+            Self::InvalidArgument {
+                command,
+                arg,
+                value,
+            } => write!(f, "/{command}: invalid <{arg}> {value}"),
+        }
+    }
+}
+
+pub struct ParsedCommand {
+    pub spec: &'static CommandSpec,
+    pub args: Vec<String>,
+}
+
+impl ParsedCommand {
+    pub fn run(self, app: &mut AppState, handle: &ezsockets::Client<client::Client>) {
+        (self.spec.handler)(app, handle, self.args)
+    }
+}
+
+impl CommandSpec {
+    // e.g. "/join <room>...", optional arguments in square brackets
+    pub fn usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+        for arg in self.args {
+            let repeat = if arg.variadic { "..." } else { "" };
+            if arg.required {
+                usage.push_str(&format!(" <{}>{}", arg.name, repeat));
+            } else {
+                usage.push_str(&format!(" [{}]{}", arg.name, repeat));
+            }
+        }
+        usage
+    }
+
+    fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        std::iter::once(self.name).chain(self.aliases.iter().copied())
+    }
+
+    fn arg_at(&self, index: usize) -> Option<&ArgSpec> {
+        match self.args.get(index) {
+            Some(arg) => Some(arg),
+            None => self.args.last().filter(|arg| arg.variadic),
+        }
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<(), CommandError> {
+        for (index, value) in args.iter().enumerate() {
+            let Some(arg) = self.arg_at(index) else {
+                return Err(CommandError::TooManyArguments {
+                    command: self.name.to_string(),
+                });
+            };
+
+            let is_valid = match arg.kind {
+                ArgKind::Number => value.parse::<usize>().is_ok(),
+                ArgKind::Room => is_valid_room_or_username(value),
+                ArgKind::Text => !value.trim().is_empty(),
+                ArgKind::Username => is_valid_room_or_username(value.trim_start_matches('@')),
+            };
+
+            if !is_valid {
+                return Err(CommandError::InvalidArgument {
+                    command: self.name.to_string(),
+                    arg: arg.name.to_string(),
+                    value: value.clone(),
+                });
+            }
+        }
+
+        if let Some(missing) = self.args.iter().skip(args.len()).find(|arg| arg.required) {
+            return Err(CommandError::MissingArgument {
+                command: self.name.to_string(),
+                arg: missing.name.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .find(|command| command.names().any(|n| n == name))
+}
+
+// Parse input such as `/join lobby "ghost-town"` into its command and validated arguments
+pub fn parse_command(input: &str) -> Result<ParsedCommand, CommandError> {
+    let input = input.strip_prefix('/').unwrap_or(input);
+    let mut words = split_args(input)?.into_iter();
+
+    let name = words.next().ok_or(CommandError::Empty)?;
+    let spec = find_command(&name).ok_or(CommandError::Unknown(name))?;
+    let args: Vec<String> = words.collect();
+    spec.validate_args(&args)?;
+
+    Ok(ParsedCommand { spec, args })
+}
+
+// Split on whitespace, except within double or single quotes, which are removed
+fn split_args(input: &str) -> Result<Vec<String>, CommandError> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+
+    for c in input.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => args.extend(current.take()),
+            None => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(CommandError::UnterminatedQuote);
+    }
+    args.extend(current);
+
+    Ok(args)
+}
+
+// Lines for the help screen, one per visible command
+pub fn help_lines() -> Vec<String> {
+    COMMANDS
+        .iter()
+        .filter(|command| !command.hidden)
+        .map(|command| {
+            let aliases: Vec<String> = command.aliases.iter().map(|a| format!("/{a}")).collect();
+            if aliases.is_empty() {
+                format!("  {}: {}", command.usage(), command.help)
+            } else {
+                format!(
+                    "  {}: {} (also {})",
+                    command.usage(),
+                    command.help,
+                    aliases.join(", ")
+                )
+            }
+        })
+        .collect()
+}
+
+// Complete the word being typed: a command name, or a room or username argument. Returns the new
+// input and, if the completion is ambiguous, the candidates.
+pub fn complete(app: &AppState, input: &str) -> Option<(String, Vec<String>)> {
+    let without_slash = input.strip_prefix('/')?;
+
+    // no completion within quotes
+    if without_slash.contains(['"', '\'']) {
+        return None;
+    }
+
+    let words: Vec<&str> = without_slash.split_whitespace().collect();
+    let typing_new_word = without_slash.is_empty() || without_slash.ends_with(char::is_whitespace);
+    let (prefix, word_index) = if typing_new_word {
+        ("", words.len())
+    } else {
+        (*words.last()?, words.len() - 1)
+    };
+
+    let candidates: Vec<String> = if word_index == 0 {
+        COMMANDS
+            .iter()
+            .filter(|command| !command.hidden)
+            .flat_map(|command| command.names())
+            .map(str::to_string)
+            .collect()
+    } else {
+        let arg = find_command(words[0])?.arg_at(word_index - 1)?;
+        match arg.kind {
+            ArgKind::Room => app.get_rooms().into_iter().map(|room| room.name).collect(),
+            ArgKind::Username if prefix.starts_with('@') => app
+                .get_users()
+                .into_iter()
+                .map(|user| format!("@{}", user.username))
+                .collect(),
+            ArgKind::Username => app
+                .get_users()
+                .into_iter()
+                .map(|user| user.username)
+                .collect(),
+            ArgKind::Number | ArgKind::Text => Vec::new(),
+        }
+    };
+
+    let mut matches: Vec<String> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(prefix))
+        .collect();
+    matches.sort();
+    matches.dedup();
+
+    let completed_word = match matches.as_slice() {
+        [] => return None,
+        [only] => format!("{only} "),
+        [first, rest @ ..] => rest.iter().fold(first.clone(), |common, candidate| {
+            common
+                .chars()
+                .zip(candidate.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        }),
+    };
+
+    let completed_input = format!("{}{}", &input[..input.len() - prefix.len()], completed_word);
+    let candidates = if matches.len() > 1 {
+        matches
+    } else {
+        Vec::new()
+    };
+
+    Some((completed_input, candidates))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_are_split_on_whitespace_outside_quotes() {
+        assert_eq!(
+            split_args("msg @casper \"boo to you\" ''"),
+            Ok(vec![
+                "msg".to_string(),
+                "@casper".to_string(),
+                "boo to you".to_string(),
+                "".to_string(),
+            ])
+        );
+        assert_eq!(split_args("msg 'boo"), Err(CommandError::UnterminatedQuote));
+    }
+
+    #[test]
+    fn unique_command_is_completed() {
+        let app = AppState::default();
+        assert_eq!(complete(&app, "/he"), Some(("/help ".to_string(), vec![])));
+        assert_eq!(complete(&app, "/xyz"), None);
+    }
+
+    #[test]
+    fn ambiguous_command_lists_candidates() {
+        let app = AppState::default();
+        let (input, candidates) = complete(&app, "/l").unwrap();
+        assert_eq!(input, "/leave ");
+        assert!(candidates.is_empty());
+
+        let (input, candidates) = complete(&app, "/j").unwrap();
+        assert_eq!(input, "/j");
+        assert_eq!(candidates, vec!["j".to_string(), "join".to_string()]);
+    }
+
+    #[test]
+    fn room_argument_is_completed() {
+        let mut app = AppState::default();
+        app.join_room("haunted".to_string());
+        assert_eq!(
+            complete(&app, "/join hau"),
+            Some(("/join haunted ".to_string(), vec![]))
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::bots::BotConfig;
+use crate::error::{BooError, BooResult};
+use crate::history::HistoryConfig;
+use crate::socket::DEV_URL;
+use crate::ui::styles::Theme;
+use crate::ui::timestamps::TimestampFormat;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// This module contains code for loading the config file, `$XDG_CONFIG_HOME/boo/config.toml`
+/// (or `~/.config/boo/config.toml`), which holds named profiles:
+///
+/// ```toml
+/// default_profile = "work"
+///
+/// [profiles.work]
+/// url = "wss://chat.example.com"
+/// username = "casper"
+/// rooms = ["lobby", "builds"]   # the first room is the one joined during onboarding
+/// log_level = "info"
+/// theme = "monochrome"
+/// timestamps = "full"        # "off", "time" (the default, HH:MM) or "full" (with the date)
+/// max_input_lines = 10       # the input grows with a multi-line message, 6 lines by default
+/// max_message_length = 500   # in characters, for a server which accepts longer messages
+///
+/// [profiles.work.history]   # see the history module
+/// retention_days = 7         # entered lines (for Ctrl-P, Ctrl-R) are kept here too, even
+///                            # with enabled = false
+///
+/// [[profiles.work.bots]]     # see the bots module
+/// kind = "echo"
+/// ```
+///
+/// Every field is optional. The NAME, ROOM, URL, DEV and LOG env vars take precedence over the
+/// selected profile, and command line arguments over both.
+
+const CONFIG_DIR: &str = "boo";
+const CONFIG_FILE: &str = "config.toml";
+const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    default_profile: Option<String>,
+    profiles: HashMap<String, Profile>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub url: Option<String>,
+    pub username: Option<String>,
+    pub rooms: Vec<String>,
+    pub log_level: Option<String>,
+    pub theme: Theme,
+    pub timestamps: TimestampFormat,
+    pub max_input_lines: Option<u16>,
+    pub max_message_length: Option<usize>,
+    pub history: HistoryConfig,
+    pub bots: Vec<BotConfig>,
+}
+
+impl Profile {
+    // Make room the first room, i.e. the one joined during onboarding
+    pub fn set_room(&mut self, room: String) {
+        self.rooms.retain(|profile_room| *profile_room != room);
+        self.rooms.insert(0, room);
+    }
+
+    fn apply_env_overrides(&mut self) {
+        if let Ok(username) = env::var("NAME") {
+            self.username = Some(username);
+        }
+        if let Ok(room) = env::var("ROOM") {
+            self.set_room(room);
+        }
+        if let Ok(url) = env::var("URL") {
+            self.url = Some(url);
+        }
+        if env::var("DEV").unwrap_or_default() == "true" {
+            self.url = Some(DEV_URL.to_string());
+        }
+        if let Ok(log_level) = env::var("LOG") {
+            self.log_level = Some(log_level);
+        }
+    }
+}
+
+// Load the named profile, or the config's default profile when no name is given, with the env
+// vars applied on top. A missing config file is not an error, but asking for a profile which
+// doesn't exist is.
+pub fn load_profile(name: Option<&str>) -> BooResult<Profile> {
+    let config = load_config()?;
+
+    let mut profile = match name.or(config.default_profile.as_deref()) {
+        Some(name) => config
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| BooError::Config(format!("no profile named {name}")))?,
+        None => config
+            .profiles
+            .get(DEFAULT_PROFILE)
+            .cloned()
+            .unwrap_or_default(),
+    };
+
+    profile.apply_env_overrides();
+    Ok(profile)
+}
+
+fn load_config() -> BooResult<Config> {
+    let Some(path) = get_config_path() else {
+        return Ok(Config::default());
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(BooError::Config(format!("{}: {e}", path.display()))),
+    };
+
+    toml::from_str(&contents).map_err(|e| BooError::Config(format!("{}: {e}", path.display())))
+}
+
+fn get_config_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_home.join(CONFIG_DIR).join(CONFIG_FILE))
+}
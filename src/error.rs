@@ -0,0 +1,69 @@
+use crate::socket::request::Request;
+use std::fmt;
+
+/// This module contains the `BooError` type used for all errors within the app.
+/// Errors that happen while the TUI is running are shown in the chat pane as a
+/// `Message::SystemError` instead of taking the terminal down with them.
+
+pub type BooResult<T> = Result<T, BooError>;
+
+#[derive(Debug)]
+pub enum BooError {
+    Config(String),
+    History(String),
+    Io(std::io::Error),
+    Logging(fern::InitError),
+    InvalidArgument(String),
+    InvalidUrl(String),
+    MalformedFrame { event: String, reason: String }, // server sent a message we can't parse
+    RequestNotSent(Request), // socket client is gone, the request is handed back
+    Serialize(serde_json::Error),
+    Socket(String),
+}
+
+impl fmt::Display for BooError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(reason) => write!(f, "invalid config: {reason}"),
+            Self::History(reason) => write!(f, "chat history error: {reason}"),
+            Self::Io(e) => write!(f, "terminal error: {e}"),
+            Self::Logging(e) => write!(f, "failed to initialize logging: {e}"),
+            Self::InvalidArgument(reason) => write!(f, "invalid argument: {reason}"),
+            Self::InvalidUrl(reason) => write!(f, "invalid server URL: {reason}"),
+            Self::MalformedFrame { event, reason } => {
+                write!(f, "ignored malformed {event} from server: {reason}")
+            }
+            Self::RequestNotSent(request) => {
+                write!(f, "{} request not sent, socket is closed", request.event())
+            }
+            Self::Serialize(e) => write!(f, "failed to serialize request: {e}"),
+            Self::Socket(reason) => write!(f, "socket error: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for BooError {}
+
+impl From<std::io::Error> for BooError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<fern::InitError> for BooError {
+    fn from(error: fern::InitError) -> Self {
+        Self::Logging(error)
+    }
+}
+
+impl From<rusqlite::Error> for BooError {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::History(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for BooError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Serialize(error)
+    }
+}
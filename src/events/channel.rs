@@ -1,7 +1,17 @@
 use crate::app::log::Log;
+use crate::app::message::DeliveryStatus;
 use crate::app::{AppState, SocketStatus};
+use crate::bots::BotEvent;
+use crate::logging::set_log_prefix;
 use crate::socket::client::SocketEvent;
-use crate::socket::response::Response;
+use crate::socket::pending::ReplyStatus;
+use crate::socket::request::{direct_room, Request, Shout as ShoutRequest};
+use crate::socket::response::{Response, ResponseMeta};
+use log::{debug, warn};
+
+// reason of the error reply to a push on a channel the server doesn't know, e.g. one rejoined
+// since, the push was dropped without being handled
+const UNMATCHED_TOPIC: &str = "unmatched topic";
 
 // TODO: move response handling
 // fn handle_socket_response(app: &mut AppState, json_data: String) {
@@ -12,44 +22,308 @@ pub fn handle_socket_event(app: &mut AppState, socket_event: SocketEvent) {
 
     match socket_event {
         SocketEvent::Close => app.socket_status = SocketStatus::Closed,
-        SocketEvent::Connect => app.socket_status = SocketStatus::Connected,
+        SocketEvent::Connect => {
+            // channels don't survive the connection, so rejoin every room we were in
+            let is_reconnect = app.set_socket_connected();
+            if is_reconnect && !app.get_joined_room_names().is_empty() {
+                app.rejoin_all_rooms();
+                app.add_system_internal_message("reconnected, rejoining rooms".to_string());
+            }
+        }
         SocketEvent::ConnectFail => app.socket_status = SocketStatus::ConnectFailed,
         SocketEvent::Disconnect => app.socket_status = SocketStatus::Disconnected,
+        SocketEvent::Error(error) => app.report_error(error),
+        SocketEvent::Reply(request, status) => handle_reply(app, request, status),
         SocketEvent::Response(response) => {
-            match response {
-                Response::Unknown => (),
-                _ => app.append_log(Log::new(response.clone())),
-            }
+            app.append_log(Log::new(response.clone()));
 
             match response {
-                Response::JoinReply(reply) => {
-                    app.user.online_at = reply.user.online_at;
+                Response::ChannelClose(meta, close) => {
+                    // we remove rooms before leaving, so a close for a joined room came from the server
+                    if let Some(room) = current_channel_room(app, &meta) {
+                        let reason = match close.reason {
+                            Some(reason) => format!("channel closed by server: {reason}"),
+                            None => "channel closed by server".to_string(),
+                        };
+                        app.add_system_internal_message(format!("#{}: {}", room, reason));
+                        app.set_room_errored(&room, reason);
+                    }
+                }
+                Response::ChannelError(meta, error) => {
+                    if let Some(room) = current_channel_room(app, &meta) {
+                        let reason = match error.reason {
+                            Some(reason) => format!("channel error: {reason}"),
+                            None => "channel error".to_string(),
+                        };
+                        app.add_system_internal_message(format!(
+                            "#{}: {}, rejoining",
+                            room, reason
+                        ));
+                        app.set_room_errored(&room, reason);
+                        app.schedule_rejoin(&room);
+                    }
+                }
+                Response::Direct(meta, direct) => {
+                    // until we join the conversation's channel, it arrives on one of our rooms'
+                    if current_channel_room(app, &meta).is_none()
+                        || direct.from.uuid == app.user.uuid
+                    {
+                        return;
+                    }
+                    // a message of someone else's conversation, broadcast to a room we share
+                    if meta.topic != direct_room(&app.user.uuid, &direct.from.uuid)
+                        && direct.to != app.user.uuid
+                    {
+                        return;
+                    }
+                    let room = app.open_direct_room(direct.from.clone());
+                    let is_first_unread =
+                        room != app.get_room() && app.get_unread_count(&room) == Some(0);
+                    let from = direct.from.username.clone();
+                    app.add_user_message(&room, direct.from, direct.message, direct.sent_at);
+
+                    // the conversation may be with someone outside the active room, so say so
+                    // there until it is read
+                    if is_first_unread {
+                        let message =
+                            format!("new direct message from @{from}, /msg @{from} to reply");
+                        app.add_system_internal_message(message);
+                    }
+                }
+                Response::ErrorReply(meta, reply) => {
+                    // failed requests are surfaced through SocketEvent::Reply
+                    debug!(
+                        "error reply on topic={} reason={}",
+                        meta.topic, reply.reason
+                    );
                 }
-                Response::PresenceDiff(diff) => {
-                    for user in diff.joins {
-                        let message = format!("@{} has joined #{}", user.username, app.room);
-                        app.add_user(user);
-                        app.add_system_public_message(message);
+                Response::HeartbeatReply(_) | Response::OkReply(_) => (),
+                Response::JoinReply(meta, reply) => {
+                    if let Some(room) = current_channel_room(app, &meta) {
+                        app.user.online_at = reply.user.online_at;
+                        app.set_room_joined(&room);
                     }
-                    for user in diff.leaves {
-                        let message = format!("@{} has left {}", user.username, app.room);
-                        app.remove_user(user);
-                        app.add_system_public_message(message);
+                }
+                Response::PresenceDiff(meta, diff) => {
+                    let Some(room) = current_channel_room(app, &meta) else {
+                        return;
+                    };
+                    // joins before leaves, so a device updating its meta (a leave and a join
+                    // of the same key) doesn't show the user leaving. Users only join and leave
+                    // with their first and last device
+                    for presence in diff.joins {
+                        let Some(user) = presence.user().cloned() else {
+                            continue;
+                        };
+                        if !app.join_presence(&room, presence) {
+                            continue;
+                        }
+                        if user.uuid != app.user.uuid {
+                            app.notify_bots(BotEvent::Join {
+                                room: &room,
+                                user: &user,
+                            });
+                        }
+                        let message = format!(
+                            "@{} has joined {}",
+                            user.username,
+                            app.get_room_label(&room)
+                        );
+                        app.add_system_public_message(&room, message);
+                    }
+                    for presence in diff.leaves {
+                        let Some(user) = presence.user().cloned() else {
+                            continue;
+                        };
+                        if !app.leave_presence(&room, &presence) {
+                            continue;
+                        }
+                        if user.uuid != app.user.uuid {
+                            app.notify_bots(BotEvent::Leave {
+                                room: &room,
+                                user: &user,
+                            });
+                        }
+                        let message =
+                            format!("@{} has left {}", user.username, app.get_room_label(&room));
+                        app.add_system_public_message(&room, message);
                     }
                 }
-                Response::PresenceState(state) => {
-                    app.set_users(state.users);
+                Response::PresenceState(meta, state) => {
+                    if let Some(room) = current_channel_room(app, &meta) {
+                        app.set_presences(&room, state.presences);
+                    }
                 }
-                Response::RoomsUpdate(rooms) => {
+                Response::Rename(meta, rename) => {
+                    if let Some(room) = current_channel_room(app, &meta) {
+                        let message = format!(
+                            "@{} is now known as @{}",
+                            rename.old_username, rename.user.username
+                        );
+                        app.rename_user(&room, &rename.user);
+                        app.add_system_public_message(&room, message);
+                    }
+                }
+                Response::RoomsUpdate(_meta, rooms) => {
+                    // the rooms list is global, so it is still useful from any channel
+                    app.notify_bots(BotEvent::RoomsUpdate(&rooms));
                     app.set_rooms(rooms);
                 }
-                Response::Shout(shout) => {
+                Response::Shout(meta, shout) => {
+                    let Some(room) = current_channel_room(app, &meta) else {
+                        return;
+                    };
                     if !shout.user.uuid.eq(&app.user.uuid) {
-                        app.add_user_message(shout.user, shout.message);
+                        app.notify_bots(BotEvent::Message {
+                            room: &room,
+                            user: &shout.user,
+                            content: &shout.message,
+                        });
+                        app.add_user_message(&room, shout.user, shout.message, shout.sent_at);
                     }
                 }
-                Response::Unknown => (),
+                Response::Unknown(meta) => {
+                    debug!("unhandled response on topic={}", meta.topic);
+                }
+            }
+        }
+    }
+}
+
+// Surface failed requests to the user, successful ones need no feedback
+fn handle_reply(app: &mut AppState, request: Request, status: ReplyStatus) {
+    debug!("reply status={:?} request={:?}", status, request);
+
+    // shouts outlive the channel they were sent on, see handle_shout_reply
+    if let Request::Shout(shout) | Request::Direct(shout) = request {
+        handle_shout_reply(app, shout, status);
+        return;
+    }
+
+    // ignore replies for a room we have since left or rejoined
+    if let Some(room) = request.room() {
+        if app.get_join_ref(&room) != request.join_ref() {
+            return;
+        }
+    }
+
+    let status_is_timeout = status == ReplyStatus::Timeout;
+    let failure = match status {
+        // a join reply without the joined user in its payload, the room is joined all the same
+        ReplyStatus::Ok => {
+            if let Request::Join(join) = request {
+                app.set_room_joined(&join.room);
+            }
+            return;
+        }
+        ReplyStatus::Error(reason) => reason,
+        ReplyStatus::Timeout => "timed out".to_string(),
+    };
+
+    match request {
+        Request::Direct(_) | Request::Shout(_) => (),
+        Request::Heartbeat => warn!("heartbeat failed: {failure}"),
+        Request::Join(join) => {
+            let message = format!(
+                "failed to join {}: {}",
+                app.get_room_label(&join.room),
+                failure
+            );
+            app.add_system_internal_message(message);
+            app.set_room_errored(&join.room, failure);
+
+            // a rejected join will be rejected again, but a lost one is worth retrying. Without
+            // a rejoin, the messages queued for the room won't be sent
+            if status_is_timeout {
+                app.schedule_rejoin(&join.room);
+            } else {
+                let failed_count = app.fail_outbox(&join.room);
+                if failed_count > 0 {
+                    let message = format!(
+                        "{} message(s) to {} not sent",
+                        failed_count,
+                        app.get_room_label(&join.room)
+                    );
+                    app.add_system_internal_message(message);
+                }
+            }
+        }
+        Request::Leave(leave) => warn!("leave #{} failed: {failure}", leave.room),
+        Request::Rename(rename) => {
+            let message = format!(
+                "failed to change username to @{} in {}: {}",
+                rename.username,
+                app.get_room_label(&rename.room),
+                failure
+            );
+            app.add_system_internal_message(message);
+
+            // the rename is sent to every room, only the first rejection restores the old name
+            if app.user.username == rename.username {
+                app.user.username = rename.old_username;
+                set_log_prefix(app.user.display_name());
             }
         }
     }
 }
+
+// Mark our message as sent or failed. Shouts carry no id the server could dedupe on, so one that
+// timed out is not sent again, it may have been broadcast with only the reply lost. Only a shout
+// the channel never took (it was rejoined in the meantime) is retried
+fn handle_shout_reply(app: &mut AppState, shout: ShoutRequest, status: ReplyStatus) {
+    if !app.is_joined(&shout.room) {
+        return;
+    }
+
+    let (room, local_id) = (shout.room.clone(), shout.local_id);
+    let failure = match status {
+        ReplyStatus::Ok => {
+            app.set_message_status(&room, local_id, DeliveryStatus::Sent);
+            return;
+        }
+        ReplyStatus::Timeout => {
+            "no reply from the server, it may still have been delivered".to_string()
+        }
+        ReplyStatus::Error(reason) if reason == UNMATCHED_TOPIC && app.requeue_shout(shout) => {
+            return
+        }
+        ReplyStatus::Error(reason) => reason,
+    };
+
+    app.set_message_status(&room, local_id, DeliveryStatus::Failed);
+    let message = format!(
+        "message to {} was not delivered: {}",
+        app.get_room_label(&room),
+        failure
+    );
+    app.add_system_internal_message(message);
+}
+
+// Room of the response if it belongs to a channel we are currently joined to
+fn current_channel_room(app: &AppState, meta: &ResponseMeta) -> Option<String> {
+    if is_from_current_channel(app, meta) {
+        meta.room()
+    } else {
+        None
+    }
+}
+
+// A response is stale if its room was left, or if it carries the join_ref of a previous join.
+// Broadcasts carry no join_ref, so they are only checked against the room.
+fn is_from_current_channel(app: &AppState, meta: &ResponseMeta) -> bool {
+    let Some(room) = meta.room() else {
+        return false;
+    };
+
+    let is_current = match (app.get_join_ref(&room), &meta.join_ref) {
+        (None, _) => false,
+        (Some(_), None) => true,
+        (Some(join_ref), Some(meta_join_ref)) => join_ref == *meta_join_ref,
+    };
+
+    if !is_current {
+        debug!("ignoring stale response for topic={}", meta.topic);
+    }
+
+    is_current
+}
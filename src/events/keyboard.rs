@@ -1,42 +1,59 @@
-use crate::app::{is_blank, AppState, Focus, Onboarding, RightSidebar};
+use crate::app::{AppState, Focus, Onboarding};
+use crate::commands::{complete, help_lines, join_or_switch_to_room, parse_command};
 use crate::names::{generate_valid_room_name, generate_valid_username};
-use crate::socket::client;
+use crate::socket::{client, send_request};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use log::{debug, error};
-use lorem_rustum::LoremRustum;
-
-#[derive(Debug, Default)]
-enum Command {
-    ChangeUsername,
-    SwitchRoomsFromInput,
-    SwitchRoomsFromSelected,
-    SubmitLoremMessages,
-    #[default]
-    Unknown,
-}
 
 #[derive(Debug, Default)]
 enum KeyAction {
     #[default]
     Ignore,
-    AppendInputChar(char),
+    AcceptInputSearch,
+    CancelInputSearch,
+    CancelPendingPaste,
     ClearInput,
+    CompleteCommand,
     ConfirmRoomNameAndJoin,
     ConfirmUsername,
     CycleFocus,
-    DeleteLastInputChar,
+    DeleteInputToEnd,
+    DeleteInputToStart,
+    DeleteNextInputChar,
+    DeletePrevInputChar,
+    DeletePrevInputWord,
+    InsertInputChar(char),
+    MoveCursorLeft,
+    MoveCursorRight,
+    MoveCursorToEnd,
+    MoveCursorDown,
+    MoveCursorToStart,
+    MoveCursorUp,
+    MoveCursorWordLeft,
+    MoveCursorWordRight,
+    OpenSelectedUserConversation,
+    PopInputSearchChar,
+    PushInputSearchChar(char),
     QuitApp,
+    RecallNextInput,
+    RecallPrevInput,
     ReplaceInputWithChar(char),
     ScrollMessagesDown,
     ScrollMessagesUp,
+    SearchOlderInput,
     SelectNextRoom,
+    SelectNextUser,
     SelectPrevRoom,
+    SelectPrevUser,
+    SendPendingPaste,
     SetInputToRandomRoom,
     SetInputToRandomUsername,
-    SubmitCommand(Command),
+    StartInputSearch,
+    SubmitCommand,
     SubmitMessage,
+    SwitchToSelectedRoom,
     ToggleHelp,
     ToggleRightSidebar,
+    ToggleUserDetails,
 }
 
 pub fn handle_key_event(
@@ -44,26 +61,132 @@ pub fn handle_key_event(
     handle: &ezsockets::Client<client::Client>,
     key: KeyEvent,
 ) {
-    match parse_key_action(app, key) {
+    let key_action = parse_key_action(app, key);
+    handle_key_action(app, handle, key_action);
+}
+
+// Submit a whole line as if it was typed and entered, e.g. a line read from stdin in headless mode
+pub fn handle_input_line(
+    app: &mut AppState,
+    handle: &ezsockets::Client<client::Client>,
+    line: String,
+) {
+    app.input.set(line);
+    // rejected lines are errors, so they go to stderr rather than among the messages on stdout
+    match parse_submit_action(app) {
+        KeyAction::SubmitCommand => {
+            let input = app.input.take();
+            match parse_command(&input) {
+                // there is no help screen without the TUI, so print it
+                Ok(command) if command.spec.name == "help" => {
+                    for help_line in help_lines() {
+                        app.add_system_internal_message(help_line);
+                    }
+                }
+                Ok(command) => command.run(app, handle),
+                Err(error) => app.add_system_error_message(error.to_string()),
+            }
+        }
+        KeyAction::SubmitMessage if !app.input_is_valid_message() => {
+            app.input.clear();
+            app.add_system_error_message("message is blank or too long, not sent".to_string());
+        }
+        key_action => handle_key_action(app, handle, key_action),
+    }
+}
+
+fn handle_key_action(
+    app: &mut AppState,
+    handle: &ezsockets::Client<client::Client>,
+    key_action: KeyAction,
+) {
+    match key_action {
         KeyAction::Ignore => (),
-        KeyAction::AppendInputChar(c) => app.input.push(c),
+        KeyAction::AcceptInputSearch => app.accept_input_search(),
+        KeyAction::CancelInputSearch => app.cancel_input_search(),
+        KeyAction::CancelPendingPaste => app.cancel_pending_paste(),
         KeyAction::ClearInput => app.input.clear(),
+        KeyAction::CompleteCommand => handle_complete_command(app),
         KeyAction::ConfirmRoomNameAndJoin => handle_confirm_room_name_and_join(app, handle),
         KeyAction::ConfirmUsername => handle_confirm_username(app),
         KeyAction::CycleFocus => app.cycle_focus(),
-        KeyAction::DeleteLastInputChar => handle_delete_last_input_char(app),
-        KeyAction::QuitApp => app.quit(),
-        KeyAction::ReplaceInputWithChar(c) => app.input = c.to_string(),
+        KeyAction::DeleteInputToEnd => app.input.delete_to_end(),
+        KeyAction::DeleteInputToStart => app.input.delete_to_start(),
+        KeyAction::DeleteNextInputChar => app.input.delete_next_char(),
+        KeyAction::DeletePrevInputChar => app.input.delete_prev_char(),
+        KeyAction::DeletePrevInputWord => app.input.delete_prev_word(),
+        KeyAction::InsertInputChar(c) => app.input.insert(c),
+        KeyAction::MoveCursorLeft => app.input.move_left(),
+        KeyAction::MoveCursorRight => app.input.move_right(),
+        KeyAction::MoveCursorToEnd => app.input.move_to_end(),
+        KeyAction::MoveCursorDown => app.input.move_down(),
+        KeyAction::MoveCursorToStart => app.input.move_to_start(),
+        KeyAction::MoveCursorUp => app.input.move_up(),
+        KeyAction::MoveCursorWordLeft => app.input.move_word_left(),
+        KeyAction::MoveCursorWordRight => app.input.move_word_right(),
+        KeyAction::OpenSelectedUserConversation => handle_open_selected_user_conversation(app),
+        KeyAction::PopInputSearchChar => app.pop_input_search_char(),
+        KeyAction::PushInputSearchChar(c) => app.push_input_search_char(c),
+        KeyAction::QuitApp => app.request_quit(),
+        KeyAction::RecallNextInput => app.recall_next_input(),
+        KeyAction::RecallPrevInput => app.recall_prev_input(),
+        KeyAction::ReplaceInputWithChar(c) => app.input.set(c.to_string()),
         KeyAction::ScrollMessagesDown => app.scroll_messages_down(),
         KeyAction::ScrollMessagesUp => app.scroll_messages_up(),
+        KeyAction::SearchOlderInput => app.search_older_input(),
         KeyAction::SelectNextRoom => app.select_next_room(),
+        KeyAction::SelectNextUser => app.select_next_user(),
         KeyAction::SelectPrevRoom => app.select_prev_room(),
+        KeyAction::SelectPrevUser => app.select_prev_user(),
+        KeyAction::SendPendingPaste => app.send_pending_paste(),
         KeyAction::SetInputToRandomRoom => set_input_to_random_room(app),
         KeyAction::SetInputToRandomUsername => set_input_to_random_username(app),
-        KeyAction::SubmitCommand(command) => handle_command(command, app, handle),
-        KeyAction::SubmitMessage => handle_submit_message(app, handle),
+        KeyAction::StartInputSearch => app.start_input_search(),
+        KeyAction::SubmitCommand => handle_submit_command(app, handle),
+        KeyAction::SubmitMessage => handle_submit_message(app),
+        KeyAction::SwitchToSelectedRoom => handle_switch_to_selected_room(app, handle),
         KeyAction::ToggleHelp => app.toggle_show_help(),
         KeyAction::ToggleRightSidebar => app.toggle_right_sidebar(),
+        KeyAction::ToggleUserDetails => app.toggle_show_user_details(),
+    }
+}
+
+// Insert a paste as a whole, with bracketed paste the terminal sends it as one event instead of a
+// key event per char, so a newline in it doesn't submit the input
+pub fn handle_paste(app: &mut AppState, text: String) {
+    if app.showing_help() || app.showing_user_details() || app.get_pending_paste().is_some() {
+        return;
+    }
+
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    if app.is_searching_input() {
+        for c in text.chars().filter(|c| *c != '\n') {
+            app.push_input_search_char(c);
+        }
+        return;
+    }
+    if app.ui_focus_area != Focus::Input {
+        return;
+    }
+
+    // room names and usernames are one word, a paste replaces the generated one
+    if app.onboarding != Onboarding::Completed {
+        let name: String = text.split_whitespace().collect();
+        if should_clear_all_input(app) {
+            app.input.clear();
+        }
+        if app.is_valid_paste_for_input(&name) {
+            app.input.insert_str(&name);
+        }
+        return;
+    }
+
+    if app.is_valid_paste_for_input(&text) {
+        app.input.insert_str(&text);
+    } else if app.input.starts_with('/') || text.starts_with('/') {
+        app.add_system_internal_message("paste is too long for a command".to_string());
+    } else {
+        app.set_pending_paste(text);
     }
 }
 
@@ -75,25 +198,52 @@ fn parse_key_action(app: &mut AppState, key: KeyEvent) -> KeyAction {
         return KeyAction::ToggleHelp;
     }
 
+    // Enter messages the user, any other key closes the user details
+    if app.showing_user_details() {
+        return if key.code == KeyCode::Enter {
+            KeyAction::OpenSelectedUserConversation
+        } else {
+            KeyAction::ToggleUserDetails
+        };
+    }
+
+    // Enter sends a paste over the length limit as several messages, any other key drops it
+    if app.get_pending_paste().is_some() {
+        return if key.code == KeyCode::Enter {
+            KeyAction::SendPendingPaste
+        } else {
+            KeyAction::CancelPendingPaste
+        };
+    }
+
+    // while searching the input history, Esc cancels the search instead of quitting
+    if app.is_searching_input() {
+        return parse_input_search_action(key);
+    }
+
     if is_quit_key(key) {
         return KeyAction::QuitApp;
     }
 
     // Option + key actions
     if key.modifiers.contains(KeyModifiers::ALT) {
-        return if key.code == KeyCode::Char('h') {
-            KeyAction::ToggleHelp
-        } else if key.code == KeyCode::Char('s') {
-            KeyAction::ToggleRightSidebar
-        } else {
-            KeyAction::Ignore
+        return match key.code {
+            KeyCode::Char('h') => KeyAction::ToggleHelp,
+            KeyCode::Char('s') => KeyAction::ToggleRightSidebar,
+            KeyCode::Char('b') | KeyCode::Left => KeyAction::MoveCursorWordLeft,
+            KeyCode::Char('f') | KeyCode::Right => KeyAction::MoveCursorWordRight,
+            KeyCode::Enter => parse_newline_action(app),
+            _ => KeyAction::Ignore,
         };
     }
 
     if key.code == KeyCode::Tab {
         return if app.onboarding == Onboarding::Completed
-            && app.ui_right_sidebar_view == RightSidebar::Rooms
+            && app.ui_focus_area == Focus::Input
+            && app.input.starts_with('/')
         {
+            KeyAction::CompleteCommand
+        } else if app.onboarding == Onboarding::Completed {
             KeyAction::CycleFocus
         } else {
             KeyAction::Ignore
@@ -102,7 +252,7 @@ fn parse_key_action(app: &mut AppState, key: KeyEvent) -> KeyAction {
 
     if app.ui_focus_area == Focus::Rooms {
         return if key.code == KeyCode::Enter {
-            KeyAction::SubmitCommand(Command::SwitchRoomsFromSelected)
+            KeyAction::SwitchToSelectedRoom
         } else if key.code == KeyCode::Up || key.code == KeyCode::Char('k') {
             KeyAction::SelectPrevRoom
         } else if key.code == KeyCode::Down || key.code == KeyCode::Char('j') {
@@ -112,48 +262,78 @@ fn parse_key_action(app: &mut AppState, key: KeyEvent) -> KeyAction {
         };
     }
 
+    if app.ui_focus_area == Focus::Users {
+        return if key.code == KeyCode::Enter {
+            KeyAction::ToggleUserDetails
+        } else if key.code == KeyCode::Up || key.code == KeyCode::Char('k') {
+            KeyAction::SelectPrevUser
+        } else if key.code == KeyCode::Down || key.code == KeyCode::Char('j') {
+            KeyAction::SelectNextUser
+        } else {
+            KeyAction::Ignore
+        };
+    }
+
+    // Control + key actions, line editing and input history as in readline
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        let onboarding_completed = app.onboarding == Onboarding::Completed;
+        return match key.code {
+            KeyCode::Char('p') if onboarding_completed => KeyAction::RecallPrevInput,
+            KeyCode::Char('n') if onboarding_completed => KeyAction::RecallNextInput,
+            KeyCode::Char('r') if onboarding_completed => KeyAction::StartInputSearch,
+            KeyCode::Char('a') => KeyAction::MoveCursorToStart,
+            KeyCode::Char('e') => KeyAction::MoveCursorToEnd,
+            KeyCode::Char('b') => KeyAction::MoveCursorLeft,
+            KeyCode::Char('f') => KeyAction::MoveCursorRight,
+            KeyCode::Char('d') => KeyAction::DeleteNextInputChar,
+            KeyCode::Char('k') => KeyAction::DeleteInputToEnd,
+            KeyCode::Char('u') => KeyAction::DeleteInputToStart,
+            KeyCode::Char('w') | KeyCode::Backspace => KeyAction::DeletePrevInputWord,
+            KeyCode::Left => KeyAction::MoveCursorWordLeft,
+            KeyCode::Right => KeyAction::MoveCursorWordRight,
+            _ => KeyAction::Ignore,
+        };
+    }
+
     if key.code == KeyCode::Backspace {
         if should_clear_all_input(app) {
             return KeyAction::ClearInput;
         } else {
-            return KeyAction::DeleteLastInputChar;
+            return KeyAction::DeletePrevInputChar;
         }
     }
 
-    if key.code == KeyCode::Enter {
-        if app.input.starts_with("/") {
-            if !app.input_is_valid_command() {
-                return KeyAction::SubmitCommand(Command::Unknown);
-            }
-
-            return if app.onboarding == Onboarding::Completed {
-                if app.input.starts_with("/help") || app.input.starts_with("/?") {
-                    KeyAction::ToggleHelp
-                } else if app.input.starts_with("/join ") {
-                    KeyAction::SubmitCommand(Command::SwitchRoomsFromInput)
-                } else if app.input.starts_with("/lorem ") {
-                    KeyAction::SubmitCommand(Command::SubmitLoremMessages)
-                } else if app.input.starts_with("/quit") {
-                    KeyAction::QuitApp
-                } else if app.input.starts_with("/username") {
-                    KeyAction::SubmitCommand(Command::ChangeUsername)
-                } else {
-                    KeyAction::SubmitCommand(Command::Unknown)
-                }
-            } else {
-                KeyAction::SubmitCommand(Command::Unknown)
-            };
+    if key.code == KeyCode::Delete {
+        if should_clear_all_input(app) {
+            return KeyAction::ClearInput;
+        } else {
+            return KeyAction::DeleteNextInputChar;
         }
+    }
 
-        return match app.onboarding {
-            Onboarding::Completed => KeyAction::SubmitMessage,
-            Onboarding::ConfirmingRoom => KeyAction::ConfirmRoomNameAndJoin,
-            Onboarding::ConfirmingUsername => KeyAction::ConfirmUsername,
-        };
+    match key.code {
+        KeyCode::Left => return KeyAction::MoveCursorLeft,
+        KeyCode::Right => return KeyAction::MoveCursorRight,
+        KeyCode::Home => return KeyAction::MoveCursorToStart,
+        KeyCode::End => return KeyAction::MoveCursorToEnd,
+        _ => (),
     }
 
+    // Shift + Enter needs a terminal which tells it apart from Enter, see TerminalGuard
+    if key.code == KeyCode::Enter && key.modifiers.contains(KeyModifiers::SHIFT) {
+        return parse_newline_action(app);
+    }
+
+    if key.code == KeyCode::Enter {
+        return parse_submit_action(app);
+    }
+
+    // Up and Down move between the lines of a multi-line input, past them they scroll the chat
     if key.code == KeyCode::Up {
         return match app.onboarding {
+            Onboarding::Completed if !app.input.is_cursor_on_first_line() => {
+                KeyAction::MoveCursorUp
+            }
             Onboarding::Completed => KeyAction::ScrollMessagesUp,
             Onboarding::ConfirmingRoom => KeyAction::SetInputToRandomRoom,
             Onboarding::ConfirmingUsername => KeyAction::SetInputToRandomUsername,
@@ -162,6 +342,9 @@ fn parse_key_action(app: &mut AppState, key: KeyEvent) -> KeyAction {
 
     if key.code == KeyCode::Down {
         return match app.onboarding {
+            Onboarding::Completed if !app.input.is_cursor_on_last_line() => {
+                KeyAction::MoveCursorDown
+            }
             Onboarding::Completed => KeyAction::ScrollMessagesDown,
             Onboarding::ConfirmingRoom => KeyAction::SetInputToRandomRoom,
             Onboarding::ConfirmingUsername => KeyAction::SetInputToRandomUsername,
@@ -172,41 +355,58 @@ fn parse_key_action(app: &mut AppState, key: KeyEvent) -> KeyAction {
         if should_clear_all_input(app) {
             return KeyAction::ReplaceInputWithChar(c);
         }
-
-        match app.onboarding {
-            Onboarding::Completed => {
-                return if app.input.starts_with("/") {
-                    if app.is_valid_next_char_for_input_command(c) {
-                        KeyAction::AppendInputChar(c)
-                    } else {
-                        KeyAction::Ignore
-                    }
-                } else if app.is_valid_next_char_for_input_message(c) {
-                    KeyAction::AppendInputChar(c)
-                } else {
-                    KeyAction::Ignore
-                }
-            }
-            Onboarding::ConfirmingRoom => {
-                return if app.is_valid_next_char_for_room_name(c) {
-                    KeyAction::AppendInputChar(c)
-                } else {
-                    KeyAction::Ignore
-                }
-            }
-            Onboarding::ConfirmingUsername => {
-                return if app.is_valid_next_char_for_username(c) {
-                    KeyAction::AppendInputChar(c)
-                } else {
-                    KeyAction::Ignore
-                }
-            }
-        }
+        return parse_insert_char_action(app, c);
     }
 
     KeyAction::Ignore
 }
 
+fn parse_insert_char_action(app: &AppState, c: char) -> KeyAction {
+    let is_valid = match app.onboarding {
+        Onboarding::Completed => app.is_valid_next_char_for_input(c),
+        Onboarding::ConfirmingRoom => app.is_valid_next_char_for_room_name(c),
+        Onboarding::ConfirmingUsername => app.is_valid_next_char_for_username(c),
+    };
+
+    if is_valid {
+        KeyAction::InsertInputChar(c)
+    } else {
+        KeyAction::Ignore
+    }
+}
+
+// Shift or Alt + Enter starts a new line of the message, room names and usernames are one line
+fn parse_newline_action(app: &AppState) -> KeyAction {
+    match app.onboarding {
+        Onboarding::Completed => parse_insert_char_action(app, '\n'),
+        Onboarding::ConfirmingRoom | Onboarding::ConfirmingUsername => KeyAction::Ignore,
+    }
+}
+
+// Enter submits the input, what that means depends on the onboarding step and the input itself
+fn parse_submit_action(app: &AppState) -> KeyAction {
+    match app.onboarding {
+        Onboarding::Completed if app.input.starts_with('/') => KeyAction::SubmitCommand,
+        Onboarding::Completed => KeyAction::SubmitMessage,
+        Onboarding::ConfirmingRoom => KeyAction::ConfirmRoomNameAndJoin,
+        Onboarding::ConfirmingUsername => KeyAction::ConfirmUsername,
+    }
+}
+
+// Typing refines the search and Ctrl-R finds an older match, Enter or any other key takes the
+// match as the input, to be edited or sent
+fn parse_input_search_action(key: KeyEvent) -> KeyAction {
+    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Char('r') if control => KeyAction::SearchOlderInput,
+        KeyCode::Char('c') | KeyCode::Char('g') if control => KeyAction::CancelInputSearch,
+        KeyCode::Esc => KeyAction::CancelInputSearch,
+        KeyCode::Backspace => KeyAction::PopInputSearchChar,
+        KeyCode::Char(c) if !control => KeyAction::PushInputSearchChar(c),
+        _ => KeyAction::AcceptInputSearch,
+    }
+}
+
 fn is_quit_key(key: KeyEvent) -> bool {
     key.code == KeyCode::Esc
         || (key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c'))
@@ -217,113 +417,72 @@ fn is_quit_key(key: KeyEvent) -> bool {
 //   - confirming username and input is the username, or
 fn should_clear_all_input(app: &mut AppState) -> bool {
     match app.onboarding {
-        Onboarding::ConfirmingRoom if app.input == app.room => true,
-        Onboarding::ConfirmingUsername if app.input == app.user.username => true,
+        Onboarding::ConfirmingRoom if app.input.as_str() == app.room => true,
+        Onboarding::ConfirmingUsername if app.input.as_str() == app.user.username => true,
         _ => false,
     }
 }
 
 // KeyAction handlers
 
-fn handle_submit_message(app: &mut AppState, handle: &ezsockets::Client<client::Client>) {
+// queue message in the outbox, it is sent once the room's channel is joined (see handle_events)
+fn handle_submit_message(app: &mut AppState) {
     if !app.input_is_valid_message() {
         return;
     }
 
-    // Handle normal messages
-    app.add_user_message(app.user.clone(), app.input.clone());
-
-    // send shout request
-    let message = app.input.clone();
-    let request = app.shout_request(message);
-    app.set_socket_activity();
-    handle.call(request).expect("shout request error");
+    let message = app.input.take();
+    app.add_to_input_history(message.clone());
+    app.queue_user_message(message);
+}
 
-    // clear input
-    app.input.clear();
+// run the command through the registry, on error the input is kept so it can be fixed
+fn handle_submit_command(app: &mut AppState, handle: &ezsockets::Client<client::Client>) {
+    app.add_to_input_history(app.input.to_string());
+    match parse_command(app.input.as_str()) {
+        Ok(command) => {
+            app.input.clear();
+            command.run(app, handle);
+        }
+        Err(error) => app.add_system_error_message(error.to_string()),
+    }
 }
 
-fn handle_delete_last_input_char(app: &mut AppState) {
-    app.input.pop();
+// complete the command or argument before the cursor, listing the candidates if ambiguous
+fn handle_complete_command(app: &mut AppState) {
+    if let Some((completed_input, candidates)) = complete(app, app.input.before_cursor()) {
+        app.input.set_before_cursor(completed_input);
+        if !candidates.is_empty() {
+            app.add_system_internal_message(candidates.join("  "));
+        }
+    }
 }
 
-fn handle_command(
-    command: Command,
-    app: &mut AppState,
-    handle: &ezsockets::Client<client::Client>,
-) {
-    match command {
-        Command::ChangeUsername => {
-            // TODO: change username
-            //   1. push this message uniquely, e.g. "user x has changed their name to y"
-            //   2. the server needs to handle the change too
-            //   3. the rx.try_recv() also has to handle the name change broadcast
+// join (or switch to) the room selected in the rooms list, then return focus to the input
+fn handle_switch_to_selected_room(app: &mut AppState, handle: &ezsockets::Client<client::Client>) {
+    if let Some(new_room) = app.get_selected_room_name() {
+        if new_room != app.room {
+            join_or_switch_to_room(app, handle, new_room);
+            app.ui_focus_area = Focus::Input;
         }
-        Command::SubmitLoremMessages => {
-            let prefix = "/lorem ";
-            let num_lines = app.input.trim()[prefix.len()..]
-                .parse::<usize>()
-                .unwrap_or(1);
+    }
+}
 
-            app.input.clear();
+// open (or switch to) the direct conversation with the user selected in the users list
+fn handle_open_selected_user_conversation(app: &mut AppState) {
+    if app.showing_user_details() {
+        app.toggle_show_user_details();
+    }
 
-            for _ in 0..num_lines {
-                let message = LoremRustum::new(28).to_string();
-                app.add_user_message(app.user.clone(), message);
-            }
-        }
-        Command::SwitchRoomsFromInput => {
-            // Note: similar logic to SwitchRoomsFromSelected, except:
-            // - use room from input
-            // - clear input
-            let prefix = "/join ";
-            let new_room = app.input.trim()[prefix.len()..].to_string();
-            if !is_blank(&new_room) && new_room != app.room {
-                app.input.clear();
-                let leave_request = app.leave_request();
-                debug!("sending leave request={:?}", leave_request);
-                app.set_socket_activity();
-                match handle.call(leave_request) {
-                    Ok(_) => {
-                        let join_request = app.join_new_room_request(new_room.clone());
-                        app.set_socket_activity();
-                        handle.call(join_request).expect("join error");
-                        app.room = new_room;
-                        app.set_selected_to_current_room();
-                    }
-                    Err(error) => error!("leave error: {:?}", error),
-                }
-            }
-        }
-        Command::SwitchRoomsFromSelected => {
-            // Note: similar logic to SwitchRoomsFromInput, except:
-            // - use room selected in rooms list without clearing input
-            // - change focus after join
-            if let Some(new_room) = app.get_selected_room_name() {
-                if new_room != app.room {
-                    let leave_request = app.leave_request();
-                    debug!("sending leave request={:?}", leave_request);
-                    app.set_socket_activity();
-                    match handle.call(leave_request) {
-                        Ok(_) => {
-                            let message = format!("joining #{}", new_room);
-                            app.add_system_internal_message(message);
-
-                            let join_request = app.join_new_room_request(new_room.clone());
-                            app.set_socket_activity();
-                            handle.call(join_request).expect("join error");
-
-                            app.room = new_room;
-                            app.set_selected_to_current_room();
-                            app.ui_focus_area = Focus::Input;
-                        }
-                        Err(error) => error!("leave error: {:?}", error),
-                    }
-                }
-            }
-        }
-        Command::Unknown => (),
+    let Some(peer) = app.get_selected_user() else {
+        return;
+    };
+    if peer.uuid == app.user.uuid {
+        return;
     }
+    let room = app.open_direct_room(peer);
+    app.switch_to_room(room);
+    app.ui_focus_area = Focus::Input;
 }
 
 // KeyAction handlers: Onboarding
@@ -336,14 +495,17 @@ fn handle_confirm_room_name_and_join(
     if !app.input_is_valid_room_name() {
         return;
     }
-    app.room = app.input.clone();
+    app.join_room(app.input.to_string());
 
     let message = format!("joining #{}", app.room);
     app.add_system_internal_message(message);
 
     let request = app.join_request();
     app.set_socket_activity();
-    handle.call(request).expect("join error");
+    if let Err(error) = send_request(handle, request) {
+        app.report_error(error);
+    }
+    app.join_auto_join_rooms();
     app.advance_onboarding();
 }
 
@@ -352,16 +514,16 @@ fn handle_confirm_username(app: &mut AppState) {
     if !app.input_is_valid_username() {
         return;
     }
-    app.user.username = app.input.clone();
+    app.user.username = app.input.to_string();
     app.advance_onboarding();
 }
 
 fn set_input_to_random_room(app: &mut AppState) {
     app.room = generate_valid_room_name();
-    app.input = app.room.clone();
+    app.input.set(app.room.clone());
 }
 
 fn set_input_to_random_username(app: &mut AppState) {
     app.user.username = generate_valid_username();
-    app.input = app.user.username.clone();
+    app.input.set(app.user.username.clone());
 }
@@ -1,45 +1,144 @@
 mod channel;
 mod keyboard;
-use self::channel::handle_socket_event;
-use self::keyboard::handle_key_event;
-use crate::socket::client;
+pub use self::channel::handle_socket_event;
+pub use self::keyboard::handle_input_line;
+use self::keyboard::{handle_key_event, handle_paste};
+use crate::app::message::DeliveryStatus;
+use crate::error::{BooError, BooResult};
+use crate::socket::request::Request;
+use crate::socket::{client, send_request};
 use crate::{app::AppState, socket::client::SocketEvent};
-use crossterm::event::{self, Event};
-use log::error;
-use tokio::sync::mpsc::{self, Receiver};
+use crossterm::event::{Event, EventStream};
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::watch;
+use tokio::time::{interval, interval_at, Instant, Interval, MissedTickBehavior};
 
 /// This module contains code for handling events within the main app loop.
-/// It exposes a single `handle_events` function which handles both:
+/// `EventLoop::handle_events` waits on whichever comes first of:
 ///   - incoming messages from the server
 ///   - keyboard input from the user
+///   - the heartbeat interval
+///   - the tick interval, for timers such as the socket activity indicator, rejoin backoff and bots
+///   - a shutdown signal
+/// and reports whether the UI needs to be redrawn.
 
-pub fn handle_events(
-    handle: &ezsockets::Client<client::Client>,
-    rx: &mut Receiver<SocketEvent>,
-    app: &mut AppState,
-) -> std::io::Result<()> {
-    app.tick_socket_activity();
+pub const HEARTBEAT_INTERVAL: Duration = Duration::new(30, 0); // 30 seconds
+pub const TICK_INTERVAL: Duration = Duration::new(0, 100_000_000); // 0.1 seconds
+const SOCKET_EVENT_BATCH_SIZE: usize = 64;
 
-    // Heartbeat
-    if app.update_heartbeat_timer() {
-        let heartbeat_request = app.heartbeat_request();
-        app.set_socket_activity();
-        handle.call(heartbeat_request).expect("heartbeat error");
+pub struct EventLoop {
+    heartbeat: Interval,
+    key_events: EventStream,
+    shutdown: watch::Receiver<bool>,
+    tick: Interval,
+}
+
+impl EventLoop {
+    pub fn new(shutdown: watch::Receiver<bool>) -> Self {
+        // first heartbeat is due after one interval, not immediately
+        let mut heartbeat = interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut tick = interval(TICK_INTERVAL);
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        Self {
+            heartbeat,
+            key_events: EventStream::new(),
+            shutdown,
+            tick,
+        }
     }
 
-    // Handle incoming messages from the socket
-    match rx.try_recv() {
-        Ok(socket_event) => handle_socket_event(app, socket_event),
-        Err(mpsc::error::TryRecvError::Empty) => (),
-        Err(error) => error!("rx.try_recv error: {:?}", error),
+    // Wait for the next event and handle it, returns true if the UI needs to be redrawn
+    pub async fn handle_events(
+        &mut self,
+        handle: &ezsockets::Client<client::Client>,
+        rx: &mut Receiver<SocketEvent>,
+        app: &mut AppState,
+    ) -> BooResult<bool> {
+        let mut redraw = tokio::select! {
+            // Handle incoming messages from the socket, draining whatever else is queued
+            Some(socket_event) = rx.recv() => {
+                handle_socket_event(app, socket_event);
+                for _ in 1..SOCKET_EVENT_BATCH_SIZE {
+                    match rx.try_recv() {
+                        Ok(socket_event) => handle_socket_event(app, socket_event),
+                        Err(_) => break,
+                    }
+                }
+                true
+            }
+
+            // Handle keyboard input from the user
+            terminal_event = self.key_events.next() => match terminal_event {
+                Some(Ok(Event::Key(key))) => {
+                    handle_key_event(app, handle, key);
+                    true
+                }
+                Some(Ok(Event::Paste(text))) => {
+                    handle_paste(app, text);
+                    true
+                }
+                Some(Ok(Event::Resize(_, _))) => true,
+                Some(Ok(_)) => false,
+                Some(Err(error)) => return Err(BooError::Io(error)),
+                None => {
+                    app.quit();
+                    false
+                }
+            },
+
+            // Heartbeat
+            _ = self.heartbeat.tick() => {
+                let heartbeat_request = app.heartbeat_request();
+                app.set_socket_activity();
+                if let Err(error) = send_request(handle, heartbeat_request) {
+                    app.report_error(error);
+                }
+                true
+            }
+
+            _ = self.tick.tick() => app.tick_socket_activity() | app.tick_bots(),
+
+            _ = self.shutdown.changed() => {
+                app.quit();
+                false
+            }
+        };
+
+        redraw |= send_due_requests(handle, app);
+
+        Ok(redraw)
+    }
+}
+
+// Send requests which became due: rejoins whose backoff elapsed and queued messages of joined
+// rooms, returns true if anything was sent
+pub fn send_due_requests(handle: &ezsockets::Client<client::Client>, app: &mut AppState) -> bool {
+    let mut requests = app.take_due_rejoin_requests();
+    requests.extend(app.take_outbox_requests());
+
+    if requests.is_empty() {
+        return false;
     }
 
-    // Handle keyboard input from the user
-    if event::poll(std::time::Duration::from_millis(50))? {
-        if let Event::Key(key) = event::read()? {
-            handle_key_event(app, handle, key);
+    for request in requests {
+        app.set_socket_activity();
+        if let Err(error) = send_request(handle, request) {
+            // a shout that was never written to the socket is safe to send again
+            if let BooError::RequestNotSent(Request::Shout(shout) | Request::Direct(shout)) = &error
+            {
+                if app.requeue_shout(shout.clone()) {
+                    continue;
+                }
+                app.set_message_status(&shout.room, shout.local_id, DeliveryStatus::Failed);
+            }
+            app.report_error(error);
         }
     }
 
-    Ok(())
+    true
 }
@@ -0,0 +1,201 @@
+use crate::app::message::{DeliveryStatus, Message, SystemMessage};
+use crate::app::{is_blank, AppState};
+use crate::cli::{OutputFormat, EXIT_REQUEST_FAILED, EXIT_TIMED_OUT};
+use crate::error::{BooError, BooResult};
+use crate::events::{
+    handle_input_line, handle_socket_event, send_due_requests, HEARTBEAT_INTERVAL, TICK_INTERVAL,
+};
+use crate::socket::{close_socket, connect_socket, create_channel, leave_rooms, send_request};
+use crate::terminal::listen_for_shutdown_signals;
+use log::{error, info};
+use serde_json::{json, Value as SerdeValue};
+use std::collections::HashMap;
+use std::io::{stderr, stdout, Write};
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio::io::{stdin, AsyncBufReadExt, BufReader};
+use tokio::time::{interval, interval_at, Instant};
+
+/// This module contains the headless mode (`--headless`), a line-oriented frontend for piping and
+/// bots. Each line read from stdin is submitted like input typed into the TUI, i.e. as a message
+/// or a slash command, and messages arriving in our rooms are printed to stdout, as plain text or
+/// JSON lines. Errors go to stderr.
+///
+/// Once stdin is closed the app exits as soon as everything read has been delivered, unless
+/// `--follow` is given, in which case it keeps printing until it receives a shutdown signal.
+
+const DELIVERY_TIMEOUT: Duration = Duration::new(30, 0); // 30 seconds
+
+pub async fn run_headless(
+    mut app: AppState,
+    url: Option<&str>,
+    output_format: OutputFormat,
+    follow: bool,
+) -> BooResult<ExitCode> {
+    let (tx, mut rx) = create_channel();
+    let (handle, future) = connect_socket(tx, &mut app, url).await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = future.await {
+            error!("websocket client stopped: {e}");
+        }
+    });
+
+    app.skip_onboarding();
+
+    let mut printer = MessagePrinter::new(output_format);
+    let mut lines = BufReader::new(stdin()).lines();
+    let mut stdin_closed_at: Option<Instant> = None;
+    let mut heartbeat = interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    let mut tick = interval(TICK_INTERVAL);
+    let mut shutdown = listen_for_shutdown_signals();
+
+    while !app.quitting() {
+        tokio::select! {
+            Some(socket_event) = rx.recv() => handle_socket_event(&mut app, socket_event),
+
+            line = lines.next_line(), if stdin_closed_at.is_none() => match line {
+                Ok(Some(line)) if is_blank(&line) => (),
+                Ok(Some(line)) => handle_input_line(&mut app, &handle, line),
+                Ok(None) => stdin_closed_at = Some(Instant::now()),
+                Err(error) => {
+                    stdin_closed_at = Some(Instant::now());
+                    app.report_error(BooError::Io(error));
+                }
+            },
+
+            _ = heartbeat.tick() => {
+                if let Err(error) = send_request(&handle, app.heartbeat_request()) {
+                    app.report_error(error);
+                }
+            }
+
+            _ = tick.tick() => {
+                app.tick_bots();
+            }
+
+            _ = shutdown.changed() => app.quit(),
+        }
+
+        send_due_requests(&handle, &mut app);
+
+        if printer.print_new_messages(&app).is_err() {
+            // nobody is reading our output anymore
+            app.quit();
+        }
+
+        if let Some(stdin_closed_at) = stdin_closed_at {
+            let delivered = !app.has_messages_with_status(DeliveryStatus::Pending);
+            if !follow && (delivered || stdin_closed_at.elapsed() >= DELIVERY_TIMEOUT) {
+                app.quit();
+            }
+        }
+    }
+
+    leave_rooms(&handle, &mut rx, app.leave_all_requests()).await;
+    close_socket(handle)?;
+    info!("headless mode stopped");
+
+    let exit_code = if app.has_messages_with_status(DeliveryStatus::Pending) {
+        ExitCode::from(EXIT_TIMED_OUT)
+    } else if app.has_messages_with_status(DeliveryStatus::Failed) {
+        ExitCode::from(EXIT_REQUEST_FAILED)
+    } else {
+        ExitCode::SUCCESS
+    };
+
+    Ok(exit_code)
+}
+
+// Prints the messages added to each joined room since the last call, except the messages we sent
+// ourselves, those are what was read from stdin
+struct MessagePrinter {
+    output_format: OutputFormat,
+    printed_counts: HashMap<String, usize>,
+}
+
+impl MessagePrinter {
+    fn new(output_format: OutputFormat) -> Self {
+        Self {
+            output_format,
+            printed_counts: HashMap::new(),
+        }
+    }
+
+    fn print_new_messages(&mut self, app: &AppState) -> std::io::Result<()> {
+        let joined_rooms = app.get_joined_room_names();
+
+        // a room we left and rejoin starts with an empty history
+        self.printed_counts
+            .retain(|room, _| joined_rooms.contains(room));
+
+        let mut stdout = stdout().lock();
+        for room in joined_rooms {
+            let messages = app.get_room_messages(&room);
+            let printed_count = self.printed_counts.entry(room.clone()).or_insert(0);
+
+            for message in messages.get(*printed_count..).unwrap_or_default() {
+                match message {
+                    Message::SystemError(error) => {
+                        writeln!(stderr(), "{}: {error}", app.get_room_label(&room))?
+                    }
+                    Message::User(user_message) if user_message.local_id.is_some() => (),
+                    _ => writeln!(
+                        stdout,
+                        "{}",
+                        format_message(self.output_format, app, &room, message)
+                    )?,
+                }
+            }
+
+            *printed_count = messages.len();
+        }
+
+        stdout.flush()
+    }
+}
+
+// plain lines are labelled "#room", or "@username" for direct messages, JSON has the room itself
+fn format_message(
+    output_format: OutputFormat,
+    app: &AppState,
+    room: &str,
+    message: &Message,
+) -> String {
+    match output_format {
+        OutputFormat::Plain => match message {
+            Message::User(_) => format!("{} {message}", app.get_room_label(room)),
+            _ => format!("{} * {message}", app.get_room_label(room)),
+        },
+        OutputFormat::Json => {
+            let json_message = match message {
+                Message::SystemError(system_message) => {
+                    json_system_message(room, "error", system_message)
+                }
+                Message::SystemInternal(system_message) => {
+                    json_system_message(room, "internal", system_message)
+                }
+                Message::SystemPublic(system_message) => {
+                    json_system_message(room, "public", system_message)
+                }
+                Message::User(user_message) => json!({
+                    "room": room,
+                    "kind": "user",
+                    "username": user_message.username,
+                    "content": user_message.content,
+                    "sent_at": user_message.sent_at.to_rfc3339(),
+                }),
+            };
+            json_message.to_string()
+        }
+    }
+}
+
+fn json_system_message(room: &str, kind: &str, system_message: &SystemMessage) -> SerdeValue {
+    json!({
+        "room": room,
+        "kind": kind,
+        "content": system_message.content,
+        "sent_at": system_message.sent_at.to_rfc3339(),
+    })
+}
@@ -0,0 +1,330 @@
+use crate::app::message::{DeliveryStatus, Message, OutboxMessage, SystemMessage, UserMessage};
+use crate::error::{BooError, BooResult};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// This module contains the local chat history, a SQLite database per server in
+/// `$XDG_DATA_HOME/boo/history` (or `~/.local/share/boo/history`). Messages from others are
+/// written as they arrive, ours once the server acknowledged them. The latest page of a room is
+/// loaded when it is joined, and older pages when scrolling past the top of the chat pane.
+/// Direct conversations are kept under the other user's uuid, e.g. "@0a1b…".
+///
+/// Retention is configured per profile and applied when the history is opened:
+///
+/// ```toml
+/// [profiles.work.history]
+/// enabled = true
+/// retention_days = 30     # delete messages older than this, 0 keeps them forever
+/// max_messages = 10000    # per room, 0 for no limit
+/// ```
+///
+/// Internal messages and errors are only meant for the moment, so they are not kept.
+///
+/// Our messages not acknowledged yet are kept in an outbox until they are sent or fail, so those
+/// still queued when quitting are sent the next time their room is joined. Retention doesn't
+/// apply to them.
+///
+/// The lines entered in the input, messages and commands, are kept as well so they can be
+/// recalled in a later session (see `InputHistory`). The same retention applies to them, with
+/// at most `MAX_INPUTS` kept.
+///
+/// With `enabled = false` the database is still opened, for the entered lines and the outbox,
+/// but messages are neither saved nor loaded.
+
+const DATA_DIR: &str = "boo";
+const HISTORY_DIR: &str = "history";
+const PAGE_SIZE: usize = 50;
+pub const MAX_INPUTS: usize = 500;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        room TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        username TEXT,
+        content TEXT NOT NULL,
+        sent_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS messages_room_id ON messages (room, id);
+    CREATE TABLE IF NOT EXISTS outbox (
+        local_id INTEGER PRIMARY KEY,
+        room TEXT NOT NULL,
+        content TEXT NOT NULL,
+        written_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS inputs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        line TEXT NOT NULL,
+        entered_at INTEGER NOT NULL
+    );
+";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+    pub retention_days: u32,
+    pub max_messages: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retention_days: 30,
+            max_messages: 10_000,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct History {
+    connection: Connection,
+    enabled: bool, // false keeps messages out of the database, see HistoryConfig
+}
+
+// A page of a room's history, oldest message first. The cursor is the id of the oldest message,
+// the next (older) page is loaded from before it
+#[derive(Debug, Default)]
+pub struct HistoryPage {
+    pub messages: Vec<Message>,
+    pub cursor: Option<i64>,
+}
+
+impl History {
+    // server is the host (and port) of the socket url, each server has its own database
+    pub fn open(server: &str, config: &HistoryConfig) -> BooResult<Self> {
+        let dir = get_history_dir()
+            .ok_or_else(|| BooError::History("no data directory, HOME is not set".to_string()))?;
+        fs::create_dir_all(&dir).map_err(|error| {
+            BooError::History(format!("can't create {}: {error}", dir.display()))
+        })?;
+
+        let connection = Connection::open(dir.join(history_file_name(server)))?;
+        connection.execute_batch(SCHEMA)?;
+
+        let history = Self {
+            connection,
+            enabled: config.enabled,
+        };
+        history.apply_retention(config)?;
+        Ok(history)
+    }
+
+    // only user and public messages are kept, anything else is ignored
+    pub fn save(&self, room: &str, message: &Message) -> BooResult<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let (kind, username, content) = match message {
+            Message::User(user_message) => (
+                "user",
+                Some(user_message.username.as_str()),
+                user_message.content.as_str(),
+            ),
+            Message::SystemPublic(system_message) => {
+                ("public", None, system_message.content.as_str())
+            }
+            Message::SystemError(_) | Message::SystemInternal(_) => return Ok(()),
+        };
+
+        self.connection.execute(
+            "INSERT INTO messages (room, kind, username, content, sent_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                room,
+                kind,
+                username,
+                content,
+                message.sent_at().timestamp_millis()
+            ],
+        )?;
+        Ok(())
+    }
+
+    // the page of messages before cursor, or the latest page without one
+    pub fn load_page(&self, room: &str, cursor: Option<i64>) -> BooResult<HistoryPage> {
+        if !self.enabled {
+            return Ok(HistoryPage::default());
+        }
+        let mut statement = self.connection.prepare(
+            "SELECT id, kind, username, content, sent_at FROM messages
+             WHERE room = ?1 AND id < ?2
+             ORDER BY id DESC LIMIT ?3",
+        )?;
+
+        let rows = statement.query_map(
+            params![room, cursor.unwrap_or(i64::MAX), PAGE_SIZE as i64],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            },
+        )?;
+
+        let mut page = HistoryPage::default();
+        for row in rows {
+            let (id, kind, username, content, sent_at) = row?;
+            let sent_at = DateTime::from_timestamp_millis(sent_at).unwrap_or_else(Utc::now);
+            let message = match (kind.as_str(), username) {
+                ("user", Some(username)) => Message::User(UserMessage {
+                    username,
+                    content,
+                    sent_at,
+                    local_id: None,
+                    status: DeliveryStatus::Sent,
+                }),
+                _ => Message::SystemPublic(SystemMessage { content, sent_at }),
+            };
+            page.messages.push(message);
+            page.cursor = Some(id);
+        }
+
+        page.messages.reverse();
+        Ok(page)
+    }
+
+    pub fn save_outbox_message(
+        &self,
+        room: &str,
+        outbox_message: &OutboxMessage,
+        written_at: DateTime<Utc>,
+    ) -> BooResult<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO outbox (local_id, room, content, written_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                outbox_message.local_id as i64,
+                room,
+                outbox_message.content,
+                written_at.timestamp_millis()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_outbox_message(&self, local_id: usize) -> BooResult<()> {
+        self.connection
+            .execute("DELETE FROM outbox WHERE local_id = ?1", [local_id as i64])?;
+        Ok(())
+    }
+
+    // the messages still queued for room, in the order they were written
+    pub fn load_outbox(&self, room: &str) -> BooResult<Vec<(OutboxMessage, DateTime<Utc>)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT local_id, content, written_at FROM outbox WHERE room = ?1 ORDER BY local_id",
+        )?;
+        let rows = statement.query_map([room], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+
+        let mut outbox = Vec::new();
+        for row in rows {
+            let (local_id, content, written_at) = row?;
+            let outbox_message = OutboxMessage {
+                local_id: local_id as usize,
+                content,
+                attempts: 0,
+            };
+            let written_at = DateTime::from_timestamp_millis(written_at).unwrap_or_else(Utc::now);
+            outbox.push((outbox_message, written_at));
+        }
+        Ok(outbox)
+    }
+
+    // local ids of this session's messages start after those kept from previous sessions
+    pub fn next_local_id(&self) -> BooResult<usize> {
+        let max_id: Option<i64> =
+            self.connection
+                .query_row("SELECT MAX(local_id) FROM outbox", [], |row| row.get(0))?;
+        Ok(max_id.map_or(0, |id| id as usize + 1))
+    }
+
+    pub fn save_input(&self, line: &str) -> BooResult<()> {
+        self.connection.execute(
+            "INSERT INTO inputs (line, entered_at) VALUES (?1, ?2)",
+            params![line, Utc::now().timestamp_millis()],
+        )?;
+        Ok(())
+    }
+
+    // the latest entered lines, oldest first
+    pub fn load_inputs(&self) -> BooResult<Vec<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT line FROM inputs ORDER BY id DESC LIMIT ?1")?;
+        let mut lines = statement
+            .query_map([MAX_INPUTS as i64], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        lines.reverse();
+        Ok(lines)
+    }
+
+    fn apply_retention(&self, config: &HistoryConfig) -> BooResult<()> {
+        if config.retention_days > 0 {
+            let retention = chrono::Duration::days(config.retention_days.into());
+            let oldest_kept = (Utc::now() - retention).timestamp_millis();
+            self.connection
+                .execute("DELETE FROM messages WHERE sent_at < ?1", [oldest_kept])?;
+            self.connection
+                .execute("DELETE FROM inputs WHERE entered_at < ?1", [oldest_kept])?;
+        }
+
+        self.connection.execute(
+            "DELETE FROM inputs WHERE id NOT IN (SELECT id FROM inputs ORDER BY id DESC LIMIT ?1)",
+            [MAX_INPUTS as i64],
+        )?;
+
+        if config.max_messages > 0 {
+            self.connection.execute(
+                "DELETE FROM messages WHERE id IN (
+                     SELECT id FROM (
+                         SELECT id, ROW_NUMBER() OVER (PARTITION BY room ORDER BY id DESC) AS n
+                         FROM messages
+                     ) WHERE n > ?1
+                 )",
+                [config.max_messages as i64],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn get_history_dir() -> Option<PathBuf> {
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+
+    Some(data_home.join(DATA_DIR).join(HISTORY_DIR))
+}
+
+// e.g. "chat.haunted.host.sqlite3" or "localhost_4000.sqlite3", anything but letters, digits, dots and dashes becomes "_"
+fn history_file_name(server: &str) -> String {
+    let name: String = server
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{name}.sqlite3")
+}
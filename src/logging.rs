@@ -1,21 +1,31 @@
 use chrono::Local;
 use fern::Dispatch;
 use log::LevelFilter;
-use std::env;
+use std::sync::RwLock;
 
 const DEFAULT_LEVEL: LevelFilter = LevelFilter::Warn;
 
-pub fn setup_logging(username: String) -> Result<(), fern::InitError> {
+// Prefix of every log line, the user's display name, which changes with /nick
+static LOG_PREFIX: RwLock<String> = RwLock::new(String::new());
+
+pub fn set_log_prefix(display_name: String) {
+    if let Ok(mut prefix) = LOG_PREFIX.write() {
+        *prefix = display_name;
+    }
+}
+
+pub fn setup_logging(display_name: String, level: Option<String>) -> Result<(), fern::InitError> {
     let log_file = "logs/app.log";
+    set_log_prefix(display_name);
 
-    let level = match env::var("LOG") {
-        Ok(ref s) if s == "error" => LevelFilter::Error,
-        Ok(ref s) if s == "warn" => LevelFilter::Warn,
-        Ok(ref s) if s == "info" => LevelFilter::Info,
-        Ok(ref s) if s == "debug" => LevelFilter::Debug,
-        Ok(ref s) if s == "trace" => LevelFilter::Trace,
-        Ok(_) => DEFAULT_LEVEL,
-        Err(_) => DEFAULT_LEVEL,
+    let level = match level {
+        Some(ref s) if s == "error" => LevelFilter::Error,
+        Some(ref s) if s == "warn" => LevelFilter::Warn,
+        Some(ref s) if s == "info" => LevelFilter::Info,
+        Some(ref s) if s == "debug" => LevelFilter::Debug,
+        Some(ref s) if s == "trace" => LevelFilter::Trace,
+        Some(_) => DEFAULT_LEVEL,
+        None => DEFAULT_LEVEL,
     };
 
     // file based logging
@@ -24,7 +34,10 @@ pub fn setup_logging(username: String) -> Result<(), fern::InitError> {
             out.finish(format_args!(
                 "{} [{}] [{}] {}",
                 Local::now().format("[%Y-%m-%d %H:%M:%S]"),
-                username,
+                LOG_PREFIX
+                    .read()
+                    .map(|prefix| prefix.clone())
+                    .unwrap_or_default(),
                 record.level(),
                 message
             ))
@@ -1,54 +1,106 @@
 mod app;
+mod bots;
+mod cli;
+mod commands;
+mod config;
+mod error;
 mod events;
+mod headless;
+mod history;
 mod logging;
 mod names;
 mod socket;
+mod subcommands;
+mod terminal;
 mod ui;
 use crate::app::AppState;
-use crate::events::handle_events;
+use crate::cli::{validate_onboarding_profile, Cli};
+use crate::config::load_profile;
+use crate::error::BooResult;
+use crate::events::EventLoop;
+use crate::headless::run_headless;
 use crate::logging::setup_logging;
-use crate::socket::{close_socket, connect_socket, create_channel};
-use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
-};
-use crossterm::ExecutableCommand;
-use log::info;
+use crate::socket::{close_socket, connect_socket, create_channel, leave_rooms};
+use crate::subcommands::run_subcommand;
+use crate::terminal::{listen_for_shutdown_signals, TerminalGuard};
+use crate::ui::styles::set_theme;
+use log::{error, info};
 use ratatui::prelude::*;
 use std::io::stdout;
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> ExitCode {
+    // errors are printed once the terminal has been restored
+    match run().await {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            eprintln!("boo: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> BooResult<ExitCode> {
+    // load config profile, command line arguments take precedence
+    let cli = Cli::parse_or_exit();
+    let mut profile = load_profile(cli.profile.as_deref())?;
+    cli.apply_overrides(&mut profile);
+    if cli.skips_onboarding() {
+        validate_onboarding_profile(&cli, &profile)?;
+    }
+
     // init app state
-    let mut app = AppState::new();
+    let mut app = AppState::new(&profile);
+    set_theme(profile.theme);
 
     // init logging
-    setup_logging(app.user.display_name().clone()).expect("failed to initialize logging");
+    setup_logging(app.user.display_name().clone(), profile.log_level.clone())?;
     info!("app started");
 
+    if let Some(command) = cli.command {
+        return run_subcommand(command, app, profile.url.as_deref()).await;
+    }
+
+    if cli.headless {
+        return run_headless(app, profile.url.as_deref(), cli.output, cli.follow).await;
+    }
+
     // connect websocket
     app.set_socket_activity();
     let (tx, mut rx) = create_channel();
-    let (handle, future) = connect_socket(tx, &mut app).await;
+    let (handle, future) = connect_socket(tx, &mut app, profile.url.as_deref()).await?;
+
+    // the history is per server, so it is opened once we know which one, before joining rooms
+    app.open_history(&profile.history);
+    if cli.no_onboarding {
+        app.skip_onboarding();
+    }
 
     tokio::spawn(async move {
-        future.await.unwrap();
+        if let Err(e) = future.await {
+            error!("websocket client stopped: {e}");
+        }
     });
 
-    // ui setup
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
+    // ui setup, the terminal is restored when the guard is dropped (or on panic)
+    let terminal_guard = TerminalGuard::new()?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let mut event_loop = EventLoop::new(listen_for_shutdown_signals());
 
-    // main loop
+    // main loop, only redraw when something changed
+    terminal.draw(|f| ui::render(f, &mut app))?;
     while !app.quitting() {
-        terminal.draw(|f| ui::render(f, &mut app))?;
-        handle_events(&handle, &mut rx, &mut app)?;
+        if event_loop.handle_events(&handle, &mut rx, &mut app).await? {
+            terminal.draw(|f| ui::render(f, &mut app))?;
+        }
     }
 
     // cleanup
+    leave_rooms(&handle, &mut rx, app.leave_all_requests()).await;
     close_socket(handle)?;
-    disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
+    drop(terminal_guard);
+    info!("app stopped");
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
@@ -1,16 +1,26 @@
+use super::pending::{PendingRequests, ReplyStatus, REQUEST_TIMEOUT};
 use super::{refs::Refs, response::Response};
+use crate::error::BooError;
+use crate::socket::message::Message as SocketMessage;
 use crate::socket::request::Request;
 use async_trait::async_trait;
 use ezsockets::{client::ClientCloseMode, CloseFrame, Error as SocketError, WSError};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 use tokio::sync::mpsc;
 
 /// This module contains the `Client` struct and ezsockets client implementation.
 /// It handles internal calls and relays messages to the server.
+/// Every request sent is tracked by message_ref until its phx_reply arrives or it times out.
+
+pub const CONNECT_BACKOFF_MIN: Duration = Duration::new(1, 0); // 1 second, the reconnect_interval
+const CONNECT_BACKOFF_MAX: Duration = Duration::new(30, 0); // 30 seconds
 
 pub struct Client {
     pub handle: ezsockets::Client<Self>,
     pub tx: mpsc::Sender<SocketEvent>,
+    connect_failures: u32,
+    pending: PendingRequests,
     refs: Refs,
 }
 
@@ -19,6 +29,8 @@ impl Client {
         Self {
             handle,
             tx,
+            connect_failures: 0,
+            pending: PendingRequests::default(),
             refs: Refs::default(),
         }
     }
@@ -27,10 +39,41 @@ impl Client {
         let new_message_ref = self.refs.message_ref.fetch_add(1, Ordering::SeqCst);
 
         Refs {
-            join_ref: self.refs.join_ref.clone(),
             message_ref: AtomicUsize::new(new_message_ref + 1),
         }
     }
+
+    // Exponential backoff between reconnect attempts: 1s, 2s, 4s ... up to CONNECT_BACKOFF_MAX
+    fn connect_backoff(&self) -> Duration {
+        let exponent = self.connect_failures.saturating_sub(1).min(5);
+        (CONNECT_BACKOFF_MIN * 2u32.pow(exponent)).min(CONNECT_BACKOFF_MAX)
+    }
+
+    // Resolve a request that never made it onto the socket as an error
+    async fn reply_not_sent(&self, request: Request, error: BooError) {
+        log::error!("{error}");
+        let reply = SocketEvent::Reply(request, ReplyStatus::Error(error.to_string()));
+        if let Err(e) = self.tx.send(reply).await {
+            log::error!("error sending message to channel: {e}");
+        }
+    }
+
+    // Resolve the request with a timeout if no phx_reply arrived in time
+    fn spawn_request_timeout(&self, message_ref: usize) {
+        let pending = self.pending.clone();
+        let tx = self.tx.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(REQUEST_TIMEOUT).await;
+            if let Some(request) = pending.remove(message_ref) {
+                log::warn!("request timed out message_ref={message_ref}");
+                let reply = SocketEvent::Reply(request, ReplyStatus::Timeout);
+                if let Err(e) = tx.send(reply).await {
+                    log::error!("error sending message to channel: {e}");
+                }
+            }
+        });
+    }
 }
 
 pub enum SocketEvent {
@@ -38,6 +81,8 @@ pub enum SocketEvent {
     Connect,
     ConnectFail,
     Disconnect,
+    Error(BooError),
+    Reply(Request, ReplyStatus),
     Response(Response),
 }
 
@@ -48,8 +93,36 @@ impl ezsockets::ClientExt for Client {
     async fn on_text(&mut self, text: String) -> Result<(), SocketError> {
         log::debug!("on_text={text}");
 
+        let parsed = SocketMessage::new_from_json_string(&text)
+            .map_err(|e| BooError::MalformedFrame {
+                event: "message".to_string(),
+                reason: e.to_string(),
+            })
+            .and_then(Response::new_from_message);
+
+        let response = match parsed {
+            Ok(response) => response,
+            Err(error) => {
+                log::error!("error parsing message: {text}");
+                if let Err(e) = self.tx.send(SocketEvent::Error(error)).await {
+                    log::error!("error sending message to channel: {e}");
+                }
+                return Ok(());
+            }
+        };
+
+        // Resolve the pending request this phx_reply answers
+        if let Some(status) = response.reply_status() {
+            let message_ref = response.meta().message_ref;
+            if let Some(request) = message_ref.and_then(|r| self.pending.remove(r)) {
+                if let Err(e) = self.tx.send(SocketEvent::Reply(request, status)).await {
+                    log::error!("error sending message to channel: {e}");
+                }
+            }
+        }
+
         // Relay message from server to channel
-        let response = SocketEvent::Response(Response::new_from_json_string(&text));
+        let response = SocketEvent::Response(response);
         if let Err(e) = self.tx.send(response).await {
             log::error!("error sending message to channel: {e}");
         }
@@ -65,18 +138,33 @@ impl ezsockets::ClientExt for Client {
     async fn on_call(&mut self, request: Request) -> Result<(), SocketError> {
         log::debug!("on_call={:?}", request);
 
-        let request_payload = request.to_payload(self.next_refs());
+        let refs = self.next_refs();
+        let message_ref = refs.get_message_ref();
+        let request_payload = match request.to_payload(refs) {
+            Ok(request_payload) => request_payload,
+            Err(error) => {
+                self.reply_not_sent(request, error).await;
+                return Ok(());
+            }
+        };
         log::info!("sending request: {request_payload}");
 
-        self.handle
-            .text(request_payload)
-            .expect("error sending request");
+        self.pending.insert(message_ref, request);
+        self.spawn_request_timeout(message_ref);
+
+        if let Err(e) = self.handle.text(request_payload) {
+            if let Some(request) = self.pending.remove(message_ref) {
+                let error = BooError::Socket(format!("error sending request: {e:?}"));
+                self.reply_not_sent(request, error).await;
+            }
+        }
 
         Ok(())
     }
 
     async fn on_connect(&mut self) -> Result<(), SocketError> {
         log::debug!("on_connect");
+        self.connect_failures = 0;
 
         if let Err(e) = self.tx.send(SocketEvent::Connect).await {
             log::error!("error sending message to channel: {e}");
@@ -92,6 +180,17 @@ impl ezsockets::ClientExt for Client {
             log::error!("error sending message to channel: {e}");
         }
 
+        // wait a little longer after each consecutive failure before reconnecting. ezsockets
+        // waits the reconnect_interval (CONNECT_BACKOFF_MIN) itself, so only the rest is waited
+        // here. Calls are not handled until reconnected either way
+        self.connect_failures += 1;
+        let backoff = self.connect_backoff();
+        log::info!("reconnecting in {:?}", backoff);
+        let extra_wait = backoff.saturating_sub(CONNECT_BACKOFF_MIN);
+        if !extra_wait.is_zero() {
+            tokio::time::sleep(extra_wait).await;
+        }
+
         Ok(ClientCloseMode::Reconnect)
     }
 
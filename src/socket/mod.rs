@@ -1,21 +1,25 @@
 pub mod client;
 pub mod message;
+pub mod pending;
 pub mod refs;
 pub mod request;
 pub mod response;
 
 use crate::app::AppState;
-use crate::socket::client::Client;
+use crate::error::{BooError, BooResult};
+use crate::socket::client::{Client, CONNECT_BACKOFF_MIN};
+use crate::socket::request::Request;
 use ezsockets::ClientConfig;
-use std::env;
 use std::future::Future;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use url::Url;
 
 use self::client::SocketEvent;
 
 const DEFAULT_URL: &str = "wss://chat.haunted.host";
-const DEV_URL: &str = "ws://localhost:4000";
+pub const DEV_URL: &str = "ws://localhost:4000";
+const LEAVE_TIMEOUT: Duration = Duration::new(1, 0); // 1 second
 
 pub fn create_channel() -> (mpsc::Sender<SocketEvent>, mpsc::Receiver<SocketEvent>) {
     mpsc::channel::<SocketEvent>(32)
@@ -24,19 +28,53 @@ pub fn create_channel() -> (mpsc::Sender<SocketEvent>, mpsc::Receiver<SocketEven
 pub async fn connect_socket(
     tx: mpsc::Sender<SocketEvent>,
     app: &mut AppState,
-) -> (
+    url: Option<&str>,
+) -> BooResult<(
     ezsockets::Client<Client>,
     impl Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>,
-) {
-    let socket_url = get_socket_url();
+)> {
+    let socket_url = get_socket_url(url)?;
     log::info!("connecting to websocket {} ...", socket_url);
 
-    let config = ClientConfig::new(socket_url.clone());
+    let config = ClientConfig::new(socket_url.clone()).reconnect_interval(CONNECT_BACKOFF_MIN);
     app.set_socket_url(socket_url.clone());
-    ezsockets::connect(|handle| Client::new(handle, tx), config).await
+    Ok(ezsockets::connect(|handle| Client::new(handle, tx), config).await)
+}
+
+// Send phx_leave for every room and wait (briefly) for the replies, so the leaves are on the wire
+// before the socket is closed
+pub async fn leave_rooms(
+    handle: &ezsockets::Client<Client>,
+    rx: &mut mpsc::Receiver<SocketEvent>,
+    leave_requests: Vec<Request>,
+) {
+    let mut remaining = 0;
+    for leave_request in leave_requests {
+        match send_request(handle, leave_request) {
+            Ok(_) => remaining += 1,
+            Err(error) => log::error!("{error}"),
+        }
+    }
+
+    let wait_for_replies = async {
+        while remaining > 0 {
+            match rx.recv().await {
+                Some(SocketEvent::Reply(Request::Leave(_), _)) => remaining -= 1,
+                Some(_) => (),
+                None => break,
+            }
+        }
+    };
+
+    if tokio::time::timeout(LEAVE_TIMEOUT, wait_for_replies)
+        .await
+        .is_err()
+    {
+        log::warn!("timed out waiting for leave replies");
+    }
 }
 
-pub fn close_socket(handle: ezsockets::Client<Client>) -> std::io::Result<()> {
+pub fn close_socket(handle: ezsockets::Client<Client>) -> BooResult<()> {
     log::info!("closing websocket");
 
     match handle.close(None) {
@@ -46,33 +84,33 @@ pub fn close_socket(handle: ezsockets::Client<Client>) -> std::io::Result<()> {
         }
         Err(e) => {
             log::error!("failed to close socket={:?}", e);
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("failed to close socket={:?}", e),
-            ))
+            Err(BooError::Socket(format!("failed to close socket={:?}", e)))
         }
     }
 }
 
-fn get_socket_url() -> Url {
-    let base_url = if env::var("DEV").unwrap_or_default() == "true" {
-        DEV_URL.to_string()
-    } else if let Ok(custom_url) = env::var("URL") {
-        custom_url
-    } else {
-        DEFAULT_URL.to_string()
-    };
+// Hand request to the socket client, returns the request in the error if the client is gone
+pub fn send_request(handle: &ezsockets::Client<Client>, request: Request) -> BooResult<()> {
+    handle
+        .call(request.clone())
+        .map_err(|_| BooError::RequestNotSent(request))
+}
+
+fn get_socket_url(url: Option<&str>) -> BooResult<Url> {
+    let base_url = url.unwrap_or(DEFAULT_URL);
 
-    let mut url = Url::parse(&base_url).expect("Error parsing URL");
+    let mut url =
+        Url::parse(base_url).map_err(|e| BooError::InvalidUrl(format!("{base_url}: {e}")))?;
 
     url.set_path("/socket/websocket");
     url.set_query(Some("vsn=2.0.0"));
 
     // default to secure wss if not specified
-    match url.scheme() {
-        "ws" | "wss" => (),
-        _ => url.set_scheme("wss").unwrap(),
+    if !matches!(url.scheme(), "ws" | "wss") {
+        let scheme = url.scheme().to_string();
+        url.set_scheme("wss")
+            .map_err(|_| BooError::InvalidUrl(format!("unsupported scheme {scheme}")))?;
     }
 
-    url
+    Ok(url)
 }
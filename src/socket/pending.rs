@@ -0,0 +1,98 @@
+use crate::socket::request::Request;
+use serde_json::Value as SerdeValue;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// This module contains the `PendingRequests` map used by the client to track requests which are
+/// still waiting for their phx_reply, and the `ReplyStatus` each request is resolved to.
+
+pub const REQUEST_TIMEOUT: Duration = Duration::new(10, 0); // 10 seconds
+
+// How a request we sent was resolved
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplyStatus {
+    Ok,
+    Error(String), // reason given by the server
+    Timeout,
+}
+
+impl ReplyStatus {
+    // Parse status from a phx_reply payload, e.g.
+    // {"status":"error","response":{"reason":"unmatched topic"}}
+    pub fn from_reply_payload(payload: &SerdeValue) -> Self {
+        match payload["status"].as_str() {
+            Some("ok") => Self::Ok,
+            Some(_) => {
+                let reason = payload["response"]["reason"]
+                    .as_str()
+                    .unwrap_or("unknown reason")
+                    .to_string();
+                Self::Error(reason)
+            }
+            None => Self::Error("malformed reply".to_string()),
+        }
+    }
+}
+
+// Shared between the client and the timeout tasks it spawns, keyed by message_ref
+#[derive(Clone, Default)]
+pub struct PendingRequests(Arc<Mutex<HashMap<usize, Request>>>);
+
+impl PendingRequests {
+    pub fn insert(&self, message_ref: usize, request: Request) {
+        if let Ok(mut pending) = self.0.lock() {
+            pending.insert(message_ref, request);
+        }
+    }
+
+    // Remove and return the request, None if it was already resolved
+    pub fn remove(&self, message_ref: usize) -> Option<Request> {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|mut pending| pending.remove(&message_ref))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reply_status_is_parsed_from_payload() {
+        let ok = json!({"status": "ok", "response": {}});
+        assert_eq!(ReplyStatus::from_reply_payload(&ok), ReplyStatus::Ok);
+
+        let error = json!({"status": "error", "response": {"reason": "unmatched topic"}});
+        assert_eq!(
+            ReplyStatus::from_reply_payload(&error),
+            ReplyStatus::Error("unmatched topic".to_string())
+        );
+
+        let no_reason = json!({"status": "error", "response": {}});
+        assert_eq!(
+            ReplyStatus::from_reply_payload(&no_reason),
+            ReplyStatus::Error("unknown reason".to_string())
+        );
+
+        assert_eq!(
+            ReplyStatus::from_reply_payload(&json!({})),
+            ReplyStatus::Error("malformed reply".to_string())
+        );
+    }
+
+    #[test]
+    fn request_is_resolved_once_by_its_message_ref() {
+        let pending = PendingRequests::default();
+        pending.insert(1, Request::Heartbeat);
+        pending.insert(2, Request::Heartbeat);
+
+        assert!(pending.remove(3).is_none());
+        assert!(matches!(pending.remove(1), Some(Request::Heartbeat)));
+        // a reply after the timeout (or the other way around) finds nothing left to resolve
+        assert!(pending.remove(1).is_none());
+        assert!(pending.remove(2).is_some());
+    }
+}
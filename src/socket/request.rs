@@ -1,68 +1,203 @@
 use super::refs::Refs;
+use crate::app::message::OutboxMessage;
 use crate::app::user::User;
+use crate::error::BooResult;
 use crate::socket::message::Message as SocketMessage;
 use serde_json::{json, Value as SerdeValue};
 
-// This module contains the Request struct used to create requests to be sent to the server.
+// This module contains the Request enum used to create requests to be sent to the server.
 
 const TOPIC_PREFIX: &str = "chat:";
 
-// TODO: maybe Request should be an enum, e.g. Heartbeat, Join, Leave, Shout
+// Direct messages between two users go through a channel of their own, with a topic made of both
+// uuids, e.g. "dm:0f3c…:8a21…". The topic doubles as the name of the conversation's room.
+const DIRECT_TOPIC_PREFIX: &str = "dm:";
+
+#[derive(Clone, Debug)]
+pub enum Request {
+    Heartbeat,
+    Join(Join),
+    Direct(Shout), // a shout on a direct message channel
+    Leave(Leave),
+    Rename(Rename),
+    Shout(Shout),
+}
+
+#[derive(Clone, Debug)]
+pub struct Join {
+    pub room: String,
+    pub join_ref: String,
+    pub user: User,
+}
+
+#[derive(Clone, Debug)]
+pub struct Leave {
+    pub room: String,
+    pub join_ref: String,
+}
+
 #[derive(Clone, Debug)]
-pub struct Request {
-    pub event: String,
-    payload: SerdeValue,
-    topic: String,
+pub struct Rename {
+    pub room: String,
+    pub join_ref: String,
+    pub username: String,
+    pub old_username: String, // restored if the rename is rejected, not sent to the server
+}
+
+#[derive(Clone, Debug)]
+pub struct Shout {
+    pub room: String,
+    pub join_ref: String,
+    pub message: String,
+    pub local_id: usize, // id of the message in the room's history, not sent to the server
+    pub attempts: usize,
 }
 
 impl Request {
+    pub fn new_direct(room: String, join_ref: String, outbox_message: OutboxMessage) -> Self {
+        Self::Direct(Shout {
+            room,
+            join_ref,
+            message: outbox_message.content,
+            local_id: outbox_message.local_id,
+            attempts: outbox_message.attempts,
+        })
+    }
+
     pub fn new_heartbeat() -> Self {
-        Self {
-            topic: "phoenix".to_string(),
-            event: "heartbeat".to_string(),
-            payload: json!({}),
+        Self::Heartbeat
+    }
+
+    pub fn new_join(room: String, join_ref: String, user: User) -> Self {
+        Self::Join(Join {
+            room,
+            join_ref,
+            user,
+        })
+    }
+
+    pub fn new_leave(room: String, join_ref: String) -> Self {
+        Self::Leave(Leave { room, join_ref })
+    }
+
+    pub fn new_rename(
+        room: String,
+        join_ref: String,
+        username: String,
+        old_username: String,
+    ) -> Self {
+        Self::Rename(Rename {
+            room,
+            join_ref,
+            username,
+            old_username,
+        })
+    }
+
+    pub fn new_shout(room: String, join_ref: String, outbox_message: OutboxMessage) -> Self {
+        Self::Shout(Shout {
+            room,
+            join_ref,
+            message: outbox_message.content,
+            local_id: outbox_message.local_id,
+            attempts: outbox_message.attempts,
+        })
+    }
+
+    pub fn event(&self) -> &'static str {
+        match self {
+            Self::Direct(_) => "dm",
+            Self::Heartbeat => "heartbeat",
+            Self::Join(_) => "phx_join",
+            Self::Leave(_) => "phx_leave",
+            Self::Rename(_) => "rename",
+            Self::Shout(_) => "shout",
+        }
+    }
+
+    // Room of the channel the request is sent on, None for the "phoenix" topic
+    pub fn room(&self) -> Option<String> {
+        match self {
+            Self::Direct(shout) => Some(shout.room.clone()),
+            Self::Heartbeat => None,
+            Self::Join(join) => Some(join.room.clone()),
+            Self::Leave(leave) => Some(leave.room.clone()),
+            Self::Rename(rename) => Some(rename.room.clone()),
+            Self::Shout(shout) => Some(shout.room.clone()),
         }
     }
 
-    pub fn new_join(room: String, user: User) -> Self {
-        Self {
-            topic: room_to_topic(room),
-            event: "phx_join".to_string(),
-            payload: json!({ "user": user  }),
+    pub fn join_ref(&self) -> Option<String> {
+        match self {
+            Self::Direct(shout) => Some(shout.join_ref.clone()),
+            Self::Heartbeat => None,
+            Self::Join(join) => Some(join.join_ref.clone()),
+            Self::Leave(leave) => Some(leave.join_ref.clone()),
+            Self::Rename(rename) => Some(rename.join_ref.clone()),
+            Self::Shout(shout) => Some(shout.join_ref.clone()),
         }
     }
 
-    pub fn new_leave(room: String) -> Self {
-        Self {
-            topic: room_to_topic(room),
-            event: "phx_leave".to_string(),
-            payload: json!({}),
+    fn topic(&self) -> String {
+        match self.room() {
+            Some(room) => room_to_topic(room),
+            None => "phoenix".to_string(),
         }
     }
 
-    pub fn new_shout(room: String, message: String) -> Self {
-        Self {
-            topic: room_to_topic(room),
-            event: "shout".to_string(),
-            payload: json!({  "message": message }),
+    fn payload(&self) -> SerdeValue {
+        match self {
+            // the server knows the recipient from the topic
+            Self::Direct(shout) => json!({ "message": shout.message }),
+            Self::Heartbeat => json!({}),
+            Self::Join(join) => json!({ "user": join.user }),
+            Self::Leave(_) => json!({}),
+            Self::Rename(rename) => json!({ "username": rename.username }),
+            Self::Shout(shout) => json!({ "message": shout.message }),
         }
     }
 
-    pub fn to_payload(&self, refs: Refs) -> String {
+    pub fn to_payload(&self, refs: Refs) -> BooResult<String> {
         let message = SocketMessage {
-            join_ref: Some(refs.get_join_ref()),
+            join_ref: self.join_ref(),
             message_ref: Some(refs.get_message_ref()),
-            topic: self.topic.clone(),
-            event: self.event.clone(),
-            payload: self.payload.clone(),
+            topic: self.topic(),
+            event: self.event().to_string(),
+            payload: self.payload(),
         };
 
-        message
-            .serialize_to_json_string()
-            .expect("Problem serializing message")
+        Ok(message.serialize_to_json_string()?)
     }
 }
 
 fn room_to_topic(room: String) -> String {
-    format!("{TOPIC_PREFIX}{room}")
+    if is_direct_room(&room) {
+        room
+    } else {
+        format!("{TOPIC_PREFIX}{room}")
+    }
+}
+
+// Inverse of room_to_topic, None for non-room topics (e.g. "phoenix")
+pub fn topic_to_room(topic: &str) -> Option<String> {
+    if is_direct_room(topic) {
+        return Some(topic.to_string());
+    }
+    topic
+        .strip_prefix(TOPIC_PREFIX)
+        .map(|room| room.to_string())
+}
+
+// Room of the direct message channel between two users, the same whoever opens it
+pub fn direct_room(uuid: &str, other_uuid: &str) -> String {
+    let (first, second) = if uuid < other_uuid {
+        (uuid, other_uuid)
+    } else {
+        (other_uuid, uuid)
+    };
+    format!("{DIRECT_TOPIC_PREFIX}{first}:{second}")
+}
+
+pub fn is_direct_room(room: &str) -> bool {
+    room.starts_with(DIRECT_TOPIC_PREFIX)
 }
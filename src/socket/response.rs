@@ -1,60 +1,130 @@
 use crate::app::room::Room;
 use crate::app::user::User;
+use crate::error::{BooError, BooResult};
 use crate::socket::message::Message;
+use crate::socket::pending::ReplyStatus;
+use crate::socket::request::topic_to_room;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value as SerdeValue;
 use std::collections::HashMap;
 
-/// This module contains logic for parsing JSON from the server.
-/// Response struct exposes a single `new_from_json_string` fn which takes a JSON string and returns a `Response` enum.
+/// This module contains logic for parsing messages from the server.
+/// Response struct exposes a single `new_from_message` fn which takes a parsed socket `Message`
+/// and returns a `Response` enum.
 
-// TODO: handle PhxReply messages:
+// Server control messages look like:
 // [null,3,"phoenix","phx_reply",{"status":"ok","response":{}}]
+// ["1",2,"chat:room","phx_reply",{"status":"error","response":{"reason":"unmatched topic"}}]
+// ["1",null,"chat:room","phx_error",{}]
+// ["1",null,"chat:room","phx_close",{}]
+//
+// phx_error and phx_close may say why, e.g. {"reason":"room closed"}
+//
+// A rename is broadcast to every channel the user is in, with the user as it is after the rename:
+// [null,null,"chat:room","rename",{"user":{"uuid":"…","username":"new",…},"old_username":"old"}]
+//
+// A direct message is broadcast on the "dm:…" channel of the two users. Until the recipient has
+// joined that channel, the server pushes it on the recipient's room channels instead. "to" is the
+// uuid of the recipient:
+// [null,null,"dm:…:…","dm",{"from":{"uuid":"…","username":"casper",…},"to":"…","message":"boo"}]
+//
+// Shouts and direct messages may carry the server's time, e.g. "sent_at":"2024-10-14T09:30:00Z"
 
 // The Response enum we will build based on the event type
-#[derive(Clone, Default, Debug)]
+// Every variant carries the ResponseMeta of the message it was parsed from
+#[derive(Clone, Debug)]
 pub enum Response {
-    #[default]
-    Unknown,
-    JoinReply(JoinReply),
-    RoomsUpdate(RoomsUpdate),
-    Shout(Shout),
-    PresenceDiff(PresenceDiff),
-    PresenceState(PresenceState),
+    Unknown(ResponseMeta),
+    ChannelClose(ResponseMeta, ChannelClose),
+    ChannelError(ResponseMeta, ChannelError),
+    Direct(ResponseMeta, Direct),
+    ErrorReply(ResponseMeta, ErrorReply),
+    HeartbeatReply(ResponseMeta),
+    JoinReply(ResponseMeta, JoinReply),
+    OkReply(ResponseMeta),
+    Rename(ResponseMeta, Rename),
+    RoomsUpdate(ResponseMeta, RoomsUpdate),
+    Shout(ResponseMeta, Shout),
+    PresenceDiff(ResponseMeta, PresenceDiff),
+    PresenceState(ResponseMeta, PresenceState),
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Response::Unknown(ResponseMeta::default())
+    }
+}
+
+// The envelope of the server message: which channel it belongs to and which request it answers
+#[derive(Clone, Default, Debug)]
+pub struct ResponseMeta {
+    pub join_ref: Option<String>,
+    pub message_ref: Option<usize>,
+    pub topic: String,
+}
+
+impl ResponseMeta {
+    // Room the message was sent on, None for non-room topics (e.g. "phoenix")
+    pub fn room(&self) -> Option<String> {
+        topic_to_room(&self.topic)
+    }
 }
 
 impl Response {
-    pub fn new_from_json_string(json_data: &str) -> Self {
-        let Ok(message) = Message::new_from_json_string(json_data) else {
-            return Response::Unknown;
+    pub fn new_from_message(message: Message) -> BooResult<Self> {
+        let meta = ResponseMeta {
+            join_ref: message.join_ref,
+            message_ref: message.message_ref,
+            topic: message.topic,
         };
 
-        return match message.event.as_str() {
-            "phx_reply" => {
-                // currently only handling phx_join response.event
-                if let Ok(reply) = serde_json::from_value::<RawReply>(message.payload) {
-                    if reply.response.event == "phx_join" {
-                        return Response::JoinReply(JoinReply {
+        let event = message.event;
+        let payload = message.payload;
+
+        let response = match event.as_str() {
+            "phx_reply" => match ReplyStatus::from_reply_payload(&payload) {
+                ReplyStatus::Error(reason) => Response::ErrorReply(meta, ErrorReply { reason }),
+                _ if meta.topic == "phoenix" => Response::HeartbeatReply(meta),
+                _ => match serde_json::from_value::<RawJoinReply>(payload) {
+                    Ok(reply) if reply.response.event == "phx_join" => Response::JoinReply(
+                        meta,
+                        JoinReply {
                             user: reply.response.user,
-                        });
-                    }
-                }
-                Response::Unknown
+                        },
+                    ),
+                    _ => Response::OkReply(meta),
+                },
+            },
+            "dm" => {
+                let direct = parse_payload::<Direct>(&event, payload)?;
+                Response::Direct(meta, direct)
+            }
+            // the channel is gone whatever the payload says, so an unreadable one just has no reason
+            "phx_error" => {
+                Response::ChannelError(meta, serde_json::from_value(payload).unwrap_or_default())
+            }
+            "phx_close" => {
+                Response::ChannelClose(meta, serde_json::from_value(payload).unwrap_or_default())
             }
             "presence_diff" => {
-                let raw_diff = serde_json::from_value::<RawPresenceDiff>(message.payload).unwrap();
-                let joins = extract_first_users(raw_diff.joins);
-                let leaves = extract_first_users(raw_diff.leaves);
-                Response::PresenceDiff(PresenceDiff { joins, leaves })
+                let raw_diff = parse_payload::<RawPresenceDiff>(&event, payload)?;
+                let joins = extract_presences(raw_diff.joins);
+                let leaves = extract_presences(raw_diff.leaves);
+                Response::PresenceDiff(meta, PresenceDiff { joins, leaves })
             }
             "presence_state" => {
-                let raw_state =
-                    serde_json::from_value::<RawPresenceState>(message.payload).unwrap();
-                let users = extract_first_users(raw_state);
-                Response::PresenceState(PresenceState { users })
+                let raw_state = parse_payload::<RawPresenceState>(&event, payload)?;
+                let presences = extract_presences(raw_state);
+                Response::PresenceState(meta, PresenceState { presences })
+            }
+            "rename" => {
+                let rename = parse_payload::<Rename>(&event, payload)?;
+                Response::Rename(meta, rename)
             }
             "rooms_update" => {
-                let rooms_update =
-                    serde_json::from_value::<RawRoomsUpdate>(message.payload).unwrap();
+                let rooms_update = parse_payload::<RawRoomsUpdate>(&event, payload)?;
                 let rooms: Vec<Room> = rooms_update
                     .rooms
                     .iter()
@@ -63,17 +133,75 @@ impl Response {
                         user_count: room_update.1,
                     })
                     .collect();
-                Response::RoomsUpdate(rooms)
+                Response::RoomsUpdate(meta, rooms)
             }
             "shout" => {
-                let shout = serde_json::from_value::<Shout>(message.payload).unwrap();
-                Response::Shout(shout)
+                let shout = parse_payload::<Shout>(&event, payload)?;
+                Response::Shout(meta, shout)
             }
-            _ => Response::Unknown,
+            _ => Response::Unknown(meta),
         };
+
+        Ok(response)
+    }
+
+    pub fn meta(&self) -> &ResponseMeta {
+        match self {
+            Response::Unknown(meta)
+            | Response::ChannelClose(meta, _)
+            | Response::ChannelError(meta, _)
+            | Response::Direct(meta, _)
+            | Response::ErrorReply(meta, _)
+            | Response::HeartbeatReply(meta)
+            | Response::JoinReply(meta, _)
+            | Response::OkReply(meta)
+            | Response::Rename(meta, _)
+            | Response::RoomsUpdate(meta, _)
+            | Response::Shout(meta, _)
+            | Response::PresenceDiff(meta, _)
+            | Response::PresenceState(meta, _) => meta,
+        }
+    }
+
+    // Status of a phx_reply, None for any other message
+    pub fn reply_status(&self) -> Option<ReplyStatus> {
+        match self {
+            Response::ErrorReply(_, reply) => Some(ReplyStatus::Error(reply.reason.clone())),
+            Response::HeartbeatReply(_) | Response::JoinReply(_, _) | Response::OkReply(_) => {
+                Some(ReplyStatus::Ok)
+            }
+            _ => None,
+        }
     }
 }
 
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct ChannelClose {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct ChannelError {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Direct {
+    pub from: User,
+    #[serde(default)]
+    pub to: String,
+    pub message: String,
+    #[serde(default)]
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct ErrorReply {
+    pub reason: String,
+}
+
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct JoinReply {
     pub user: User,
@@ -81,13 +209,33 @@ pub struct JoinReply {
 
 #[derive(Clone, Default, Debug)]
 pub struct PresenceDiff {
-    pub joins: Vec<User>,
-    pub leaves: Vec<User>,
+    pub joins: Vec<Presence>,
+    pub leaves: Vec<Presence>,
 }
 
-#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct PresenceState {
-    pub users: Vec<User>,
+    pub presences: Vec<Presence>,
+}
+
+// A user "present" in a channel, with one meta per device (connection) they are present from
+#[derive(Clone, Default, Debug)]
+pub struct Presence {
+    pub key: String,
+    pub metas: Vec<User>,
+}
+
+impl Presence {
+    // the user as described by their first meta
+    pub fn user(&self) -> Option<&User> {
+        self.metas.first()
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Rename {
+    pub user: User,
+    pub old_username: String,
 }
 
 pub type RoomsUpdate = Vec<Room>;
@@ -98,16 +246,17 @@ pub type RoomsUpdate = Vec<Room>;
 pub struct Shout {
     pub user: User,
     pub message: String,
+    #[serde(default)]
+    pub sent_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Default, Serialize, Deserialize, Debug)]
-struct RawReply {
-    status: String,
-    response: RawReplyResponse,
+struct RawJoinReply {
+    response: RawJoinReplyResponse,
 }
 
 #[derive(Default, Serialize, Deserialize, Debug)]
-struct RawReplyResponse {
+struct RawJoinReplyResponse {
     event: String,
     user: User,
 }
@@ -135,13 +284,80 @@ struct UserPresence {
     metas: Vec<User>,
 }
 
-// A user can be "present" from multiple devices, we only care about the first one right now
-fn extract_first_users(joins: HashMap<String, UserPresence>) -> Vec<User> {
-    let mut users = Vec::new();
-    for (_key, user_presence) in joins {
-        if let Some(first_user) = user_presence.metas.get(0) {
-            users.push(first_user.clone());
-        }
+fn parse_payload<T: DeserializeOwned>(event: &str, payload: SerdeValue) -> BooResult<T> {
+    serde_json::from_value::<T>(payload).map_err(|e| BooError::MalformedFrame {
+        event: event.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+// Presences without metas are skipped, there is nobody behind them
+fn extract_presences(raw_presences: HashMap<String, UserPresence>) -> Vec<Presence> {
+    raw_presences
+        .into_iter()
+        .filter(|(_key, user_presence)| !user_presence.metas.is_empty())
+        .map(|(key, user_presence)| Presence {
+            key,
+            metas: user_presence.metas,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Response {
+        let message = Message::new_from_json_string(json).unwrap();
+        Response::new_from_message(message).unwrap()
+    }
+
+    #[test]
+    fn heartbeat_reply_is_ok() {
+        let response = parse(r#"[null,3,"phoenix","phx_reply",{"status":"ok","response":{}}]"#);
+        assert!(matches!(response, Response::HeartbeatReply(_)));
+        assert_eq!(response.reply_status(), Some(ReplyStatus::Ok));
+        assert_eq!(response.meta().message_ref, Some(3));
+    }
+
+    #[test]
+    fn join_reply_carries_joined_user() {
+        let response = parse(
+            r#"["1",2,"chat:haunted","phx_reply",{"status":"ok","response":{"event":"phx_join","user":{"uuid":"a","username":"casper","online_at":0}}}]"#,
+        );
+        let Response::JoinReply(meta, reply) = &response else {
+            panic!("expected a join reply, got {response:?}");
+        };
+        assert_eq!(meta.room().as_deref(), Some("haunted"));
+        assert_eq!(meta.join_ref.as_deref(), Some("1"));
+        assert_eq!(reply.user.username, "casper");
+        assert_eq!(response.reply_status(), Some(ReplyStatus::Ok));
+    }
+
+    #[test]
+    fn error_reply_carries_reason() {
+        let response = parse(
+            r#"["1",2,"chat:haunted","phx_reply",{"status":"error","response":{"reason":"unmatched topic"}}]"#,
+        );
+        assert!(
+            matches!(&response, Response::ErrorReply(_, reply) if reply.reason == "unmatched topic")
+        );
+        assert_eq!(
+            response.reply_status(),
+            Some(ReplyStatus::Error("unmatched topic".to_string()))
+        );
+    }
+
+    #[test]
+    fn channel_error_and_close_carry_optional_reason() {
+        let response = parse(r#"["1",null,"chat:haunted","phx_error",{"reason":"crashed"}]"#);
+        assert!(
+            matches!(&response, Response::ChannelError(_, error) if error.reason.as_deref() == Some("crashed"))
+        );
+        assert_eq!(response.reply_status(), None);
+
+        let response = parse(r#"["1",null,"chat:haunted","phx_close",{}]"#);
+        assert!(matches!(&response, Response::ChannelClose(_, close) if close.reason.is_none()));
+        assert_eq!(response.meta().room().as_deref(), Some("haunted"));
     }
-    users
 }
@@ -0,0 +1,135 @@
+use crate::app::AppState;
+use crate::cli::{Command, EXIT_CONNECT_FAILED, EXIT_REQUEST_FAILED, EXIT_TIMED_OUT};
+use crate::error::{BooError, BooResult};
+use crate::events::{handle_socket_event, send_due_requests};
+use crate::socket::client::{Client, SocketEvent};
+use crate::socket::pending::ReplyStatus;
+use crate::socket::request::Request;
+use crate::socket::response::Response;
+use crate::socket::{close_socket, connect_socket, create_channel, leave_rooms};
+use log::{error, info};
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio::sync::mpsc::Receiver;
+
+/// This module contains the non-interactive subcommands, e.g. `boo send` and `boo rooms`.
+/// They drive the same `AppState` and socket event handling as the TUI, without a terminal,
+/// until the request they are waiting for is answered.
+
+const SUBCOMMAND_TIMEOUT: Duration = Duration::new(15, 0); // 15 seconds
+
+enum Outcome {
+    Done,
+    ConnectFailed,
+    RequestFailed(String),
+}
+
+pub async fn run_subcommand(
+    command: Command,
+    mut app: AppState,
+    url: Option<&str>,
+) -> BooResult<ExitCode> {
+    if let Command::Send { message } = &command {
+        if !app.is_valid_message(message) {
+            return Err(BooError::InvalidArgument(
+                "message is blank or too long".to_string(),
+            ));
+        }
+    }
+
+    let (tx, mut rx) = create_channel();
+    let (handle, future) = connect_socket(tx, &mut app, url).await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = future.await {
+            error!("websocket client stopped: {e}");
+        }
+    });
+
+    // the room is joined as soon as the socket is connected, the message is sent once it is joined
+    app.skip_onboarding();
+    if let Command::Send { message } = &command {
+        app.queue_user_message(message.clone());
+    }
+
+    let outcome = tokio::time::timeout(
+        SUBCOMMAND_TIMEOUT,
+        wait_for_outcome(&command, &handle, &mut rx, &mut app),
+    )
+    .await;
+
+    leave_rooms(&handle, &mut rx, app.leave_all_requests()).await;
+    close_socket(handle)?;
+
+    let exit_code = match outcome {
+        Ok(Outcome::Done) => ExitCode::SUCCESS,
+        Ok(Outcome::ConnectFailed) => {
+            eprintln!(
+                "could not connect to {}",
+                app.socket_url.unwrap_or_default()
+            );
+            ExitCode::from(EXIT_CONNECT_FAILED)
+        }
+        Ok(Outcome::RequestFailed(reason)) => {
+            eprintln!("{reason}");
+            ExitCode::from(EXIT_REQUEST_FAILED)
+        }
+        Err(_) => {
+            eprintln!("timed out");
+            ExitCode::from(EXIT_TIMED_OUT)
+        }
+    };
+
+    info!("subcommand {:?} finished", command);
+    Ok(exit_code)
+}
+
+async fn wait_for_outcome(
+    command: &Command,
+    handle: &ezsockets::Client<Client>,
+    rx: &mut Receiver<SocketEvent>,
+    app: &mut AppState,
+) -> Outcome {
+    while let Some(socket_event) = rx.recv().await {
+        let outcome = match (&socket_event, command) {
+            (SocketEvent::ConnectFail, _) => Some(Outcome::ConnectFailed),
+            (SocketEvent::Reply(Request::Join(join), status), _) => match status {
+                ReplyStatus::Ok => None,
+                ReplyStatus::Error(reason) => Some(Outcome::RequestFailed(format!(
+                    "failed to join #{}: {reason}",
+                    join.room
+                ))),
+                ReplyStatus::Timeout => None, // the outer timeout will catch this
+            },
+            (SocketEvent::Reply(Request::Shout(_), status), Command::Send { .. }) => match status {
+                ReplyStatus::Ok => Some(Outcome::Done),
+                ReplyStatus::Error(reason) => Some(Outcome::RequestFailed(format!(
+                    "message not sent: {reason}"
+                ))),
+                ReplyStatus::Timeout => None,
+            },
+            _ => None,
+        };
+
+        let is_rooms_update = matches!(
+            socket_event,
+            SocketEvent::Response(Response::RoomsUpdate(..))
+        );
+        handle_socket_event(app, socket_event);
+
+        if let Some(outcome) = outcome {
+            return outcome;
+        }
+
+        if is_rooms_update && matches!(command, Command::Rooms) {
+            for (room, user_count) in app.get_rooms_with_counts() {
+                println!("{room}\t{user_count}");
+            }
+            return Outcome::Done;
+        }
+
+        send_due_requests(handle, app);
+    }
+
+    Outcome::RequestFailed("socket client stopped".to_string())
+}
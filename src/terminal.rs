@@ -0,0 +1,111 @@
+use crossterm::event::{
+    DisableBracketedPaste, EnableBracketedPaste, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use crossterm::ExecutableCommand;
+use std::backtrace::Backtrace;
+use std::io::stdout;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::watch;
+
+/// This module contains the `TerminalGuard` which puts the terminal in raw mode and the alternate
+/// screen, and makes sure it is restored however the app exits: normally, on panic or on signal.
+///
+/// Bracketed paste is enabled, so a paste arrives as one event rather than as typed keys.
+/// Terminals supporting the kitty keyboard protocol are also asked to disambiguate keys, so that
+/// e.g. Shift + Enter is not reported as Enter.
+
+// whether the keyboard enhancement was pushed, so it is popped on restore
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn new() -> std::io::Result<Self> {
+        install_panic_hook();
+        enable_raw_mode()?;
+        stdout().execute(EnterAlternateScreen)?;
+        stdout().execute(EnableBracketedPaste)?;
+        if supports_keyboard_enhancement().unwrap_or(false) {
+            stdout().execute(PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES,
+            ))?;
+            KEYBOARD_ENHANCED.store(true, Ordering::Relaxed);
+        }
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if let Err(e) = restore_terminal() {
+            log::error!("failed to restore terminal: {e}");
+        }
+    }
+}
+
+fn restore_terminal() -> std::io::Result<()> {
+    if KEYBOARD_ENHANCED.swap(false, Ordering::Relaxed) {
+        stdout().execute(PopKeyboardEnhancementFlags)?;
+    }
+    stdout().execute(DisableBracketedPaste)?;
+    disable_raw_mode()?;
+    stdout().execute(LeaveAlternateScreen)?;
+    Ok(())
+}
+
+// Restore the terminal before the panic message is printed, and write the report to the log file
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        log::error!("panic: {info}\n{}", Backtrace::force_capture());
+        log::logger().flush();
+        default_hook(info);
+    }));
+}
+
+// Returns a receiver which changes once SIGTERM, SIGHUP or SIGINT is received, the event loop quits on it
+pub fn listen_for_shutdown_signals() -> watch::Receiver<bool> {
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        log::info!("shutdown signal received");
+        let _ = shutdown_tx.send(true);
+    });
+
+    shutdown_rx
+}
+
+#[cfg(unix)]
+async fn wait_for_shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (Ok(mut sigterm), Ok(mut sighup), Ok(mut sigint)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::hangup()),
+        signal(SignalKind::interrupt()),
+    ) else {
+        log::error!("failed to listen for shutdown signals");
+        return std::future::pending().await;
+    };
+
+    tokio::select! {
+        _ = sigterm.recv() => (),
+        _ = sighup.recv() => (),
+        _ = sigint.recv() => (), // only without raw mode, e.g. --headless
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown_signal() {
+    if tokio::signal::ctrl_c().await.is_err() {
+        log::error!("failed to listen for shutdown signals");
+        std::future::pending::<()>().await;
+    }
+}
@@ -9,6 +9,18 @@ pub fn area_width_minus_border(area: Rect) -> u16 {
     area.width.checked_sub(2).unwrap_or(0)
 }
 
+// Rect of at most width x height, centered in area
+pub fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
 pub fn get_wrapped_line_counts<T>(area: Rect, items: &[T]) -> Vec<usize>
 where
     T: fmt::Display,
@@ -1,9 +1,10 @@
 pub mod math;
 pub mod styles;
 pub mod symbols;
+pub mod timestamps;
 pub mod widgets;
 use self::math::area_width_minus_border;
-use self::widgets::{header, help, input, messages, sidebars, users};
+use self::widgets::{header, help, input, messages, paste_prompt, sidebars, user_details, users};
 use crate::app::{AppState, RightSidebar};
 use ratatui::prelude::*;
 
@@ -79,7 +80,10 @@ pub fn render(frame: &mut Frame, app: &mut AppState) {
         .direction(Direction::Vertical)
         .spacing(0)
         .margin(0)
-        .constraints(vec![Constraint::Min(1), Constraint::Length(3)])
+        .constraints(vec![
+            Constraint::Min(1),
+            Constraint::Length(app.get_input_height() + 2),
+        ])
         .split(messages_outer_layout);
 
     let (messages_area, input_area) = (messages_layout[0], messages_layout[1]);
@@ -103,15 +107,23 @@ pub fn render(frame: &mut Frame, app: &mut AppState) {
     sidebars::render_right_sidebar_widget(frame, right_sidebar_area, app);
     input::render_widget(frame, input_area, app);
 
+    if app.showing_user_details() {
+        user_details::render_widget(frame, main_outer_layout, app);
+    }
+
+    if app.get_pending_paste().is_some() {
+        paste_prompt::render_widget(frame, main_outer_layout, app);
+    }
+
     // Cursor
     // Clamp x poition to input area width (see input::render_widget for horizontal scroll logic)
     let input_area_width = area_width_minus_border(input_area);
-    let x = if app.ui_input_width >= input_area_width {
-        input_area.x + input_area_width
-    } else {
-        input_area.x + 1 + app.ui_input_width
-    };
+    let x = input_area.x
+        + 1
+        + app
+            .ui_input_cursor_x
+            .min(input_area_width.saturating_sub(1));
 
-    let y = input_area.y + 1;
+    let y = input_area.y + 1 + app.ui_input_cursor_y;
     frame.set_cursor(x, y);
 }
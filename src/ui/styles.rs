@@ -1,7 +1,49 @@
 use ratatui::prelude::*;
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+// The theme is picked once from the config profile at startup, before the first render
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Default,
+    Monochrome, // no colors, for terminals where they are unreadable
+}
+
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+fn theme() -> Theme {
+    THEME.get().copied().unwrap_or_default()
+}
+
+pub fn get_accent_style() -> Style {
+    match theme() {
+        Theme::Default => Style::new().light_blue(),
+        Theme::Monochrome => Style::new(),
+    }
+}
+
+pub fn get_error_style() -> Style {
+    match theme() {
+        Theme::Default => Style::new().light_red(),
+        Theme::Monochrome => Style::new().underlined(),
+    }
+}
+
+pub fn get_username_style() -> Style {
+    match theme() {
+        Theme::Default => Style::new().light_green(),
+        Theme::Monochrome => Style::new().bold(),
+    }
+}
 
 pub fn get_title_style() -> Style {
-    Style::new().light_blue().not_dim()
+    get_accent_style().not_dim()
 }
 
 pub fn get_selection_style(a: &String, b: &String) -> Style {
@@ -11,3 +53,21 @@ pub fn get_selection_style(a: &String, b: &String) -> Style {
         Style::new().dim()
     }
 }
+
+// current room is bold, other joined rooms are normal, rooms we haven't joined are dimmed,
+// and rooms whose channel errored use the error style
+pub fn get_room_style(is_current: bool, is_joined: bool, is_errored: bool) -> Style {
+    let style = if is_current {
+        Style::new().not_dim().bold()
+    } else if is_joined {
+        Style::new().not_dim()
+    } else {
+        Style::new().dim()
+    };
+
+    if is_errored {
+        style.patch(get_error_style())
+    } else {
+        style
+    }
+}
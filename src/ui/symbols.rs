@@ -4,6 +4,7 @@ pub const HASH_SYMBOL: &str = "ⵌ";
 pub const LOG_SYMBOL: &str = "▤";
 pub const SOCKET_ACTIVE_SYMBOL: &str = "☷";
 pub const SOCKET_STATUS_SYMBOL: &str = "☰";
+pub const UNREAD_SYMBOL: &str = "•";
 
 // Ascii symbols
 // pub const CHAT_SYMBOL: &str = "";
@@ -11,3 +12,4 @@ pub const SOCKET_STATUS_SYMBOL: &str = "☰";
 // pub const LOG_SYMBOL: &str = "%";
 // pub const SOCKET_ACTIVE_SYMBOL: &str = "-";
 // pub const SOCKET_STATUS_SYMBOL: &str = "=";
+// pub const UNREAD_SYMBOL: &str = "*";
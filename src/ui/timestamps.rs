@@ -0,0 +1,33 @@
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::Deserialize;
+
+/// This module contains the formatting of message timestamps and of the day separators shown in
+/// the chat pane. Times are shown in local time.
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampFormat {
+    Off,
+    #[default]
+    Time, // HH:MM, the date is given by the day separators
+    Full, // date and time
+}
+
+pub fn local_date(sent_at: DateTime<Utc>) -> NaiveDate {
+    sent_at.with_timezone(&Local).date_naive()
+}
+
+// None if timestamps are off
+pub fn format_timestamp(format: TimestampFormat, sent_at: DateTime<Utc>) -> Option<String> {
+    let sent_at = sent_at.with_timezone(&Local);
+    match format {
+        TimestampFormat::Off => None,
+        TimestampFormat::Time => Some(sent_at.format("%H:%M").to_string()),
+        TimestampFormat::Full => Some(sent_at.format("%Y-%m-%d %H:%M").to_string()),
+    }
+}
+
+// e.g. "── Tuesday, 14 Oct ──"
+pub fn format_day_separator(date: NaiveDate) -> String {
+    format!("── {} ──", date.format("%A, %-d %b"))
+}
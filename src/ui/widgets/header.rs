@@ -1,6 +1,7 @@
 use crate::app::AppState;
 use crate::app::Onboarding;
 use crate::app::SocketStatus;
+use crate::ui::styles::{get_accent_style, get_error_style};
 use crate::ui::symbols::*;
 use ratatui::prelude::*;
 use ratatui::widgets::*;
@@ -22,7 +23,11 @@ pub fn render_widget(frame: &mut Frame, area: Rect, app: &AppState) {
             let room = if input == room { room } else { input };
             (username, format!(" {HASH_SYMBOL} "), room)
         }
-        Onboarding::Completed => (username, format!(" {HASH_SYMBOL} "), room),
+        // direct conversations are labelled with the other user's name
+        Onboarding::Completed => match app.get_room_label(&room).strip_prefix('@') {
+            Some(peer) => (username, " @ ".to_string(), peer.to_string()),
+            None => (username, format!(" {HASH_SYMBOL} "), room),
+        },
     };
 
     let socket_symbol = if app.is_socket_active() {
@@ -33,19 +38,26 @@ pub fn render_widget(frame: &mut Frame, area: Rect, app: &AppState) {
 
     let socket_style = match app.socket_status {
         SocketStatus::Closed => Style::default().dim(),
-        SocketStatus::Connected => Style::default().light_blue().bold(),
-        SocketStatus::ConnectFailed => Style::default().light_red().not_dim(),
-        SocketStatus::Disconnected => Style::default().light_red().not_dim(),
+        SocketStatus::Connected => get_accent_style().bold(),
+        SocketStatus::ConnectFailed => get_error_style().not_dim(),
+        SocketStatus::Disconnected => get_error_style().not_dim(),
+    };
+
+    // unread direct messages, their conversations may not be in the sidebar being shown
+    let direct_unread = match app.get_direct_unread_total() {
+        0 => "".to_string(),
+        count => format!(" {UNREAD_SYMBOL}{count}"),
     };
 
     let row = Row::new(vec![
         Cell::from(Line::from(vec![
-            Span::styled("@ ", Style::default().light_blue().bold()),
+            Span::styled("@ ", get_accent_style().bold()),
             Span::raw(username),
+            Span::styled(direct_unread, get_accent_style().not_dim()),
         ])),
         Cell::from(
             Line::from(vec![
-                Span::styled(sep, Style::default().light_blue().bold()),
+                Span::styled(sep, get_accent_style().bold()),
                 Span::raw(room),
             ])
             .alignment(Alignment::Center),
@@ -1,26 +1,42 @@
+use crate::commands::help_lines;
 use ratatui::prelude::*;
 use ratatui::widgets::*;
+use unicode_width::UnicodeWidthStr;
 
 pub fn build_widget(area: Rect) -> List<'static> {
-    let items = vec![
+    let mut items: Vec<String> = vec![
         "Welcome to the chat! 👻",
         "",
         "Keyboard Shortcuts",
         "  Esc: Quit the application",
-        "  Tab: Cycle focus from input to rooms",
+        "  Tab: Cycle focus from input to users and rooms, or complete a /command",
+        "  j/k: Select a room or user",
+        "  Enter: Switch to the selected room, or show the selected user's details",
         "  Alt + h: Show this help message",
         "  Alt + s: Toggle right sidebar view",
         "",
-        "Commands",
-        "  /?: Show this help message",
-        "  /help: Show this help message",
-        "  /join: Join a room",
-        "  /quit: Quit the application",
+        "Editing",
+        "  Shift/Alt + Enter: Start a new line of the message",
+        "  Left/Right, Home/End (Ctrl + a/e): Move the cursor",
+        "  Up/Down: Move between the lines of the message, or scroll the chat",
+        "  Alt + Left/Right (Alt + b/f): Move the cursor a word",
+        "  Delete (Ctrl + d): Delete the character under the cursor",
+        "  Ctrl + w: Delete the word before the cursor",
+        "  Ctrl + u/k: Delete to the start or end of the line",
+        "  Ctrl + p/n: Recall the previous or next line entered",
+        "  Ctrl + r: Search the lines entered",
+        "  Paste: Insert as is, or send as several messages if too long",
         "",
-        "Press any key to close this help message",
-    ];
+        "Commands",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+    items.extend(help_lines());
+    items.push("".to_string());
+    items.push("Press any key to close this help message".to_string());
 
-    let max_line_length = (items.iter().map(|line| line.len()).max().unwrap_or(0) + 2) as u16;
+    let max_line_length = (items.iter().map(|line| line.width()).max().unwrap_or(0) + 2) as u16;
     let available_padding_x = area.width.checked_sub(max_line_length).unwrap_or(0);
     let padding_x = if available_padding_x >= 2 {
         available_padding_x / 2
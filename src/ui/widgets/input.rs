@@ -1,64 +1,83 @@
 use crate::app::AppState;
 use crate::app::Focus;
 use crate::app::Onboarding;
-use crate::ui::math::area_width_minus_border;
+use crate::ui::math::{area_height_minus_border, area_width_minus_border};
+use crate::ui::styles::get_accent_style;
 use ratatui::prelude::*;
 use ratatui::widgets::*;
 
 pub fn render_widget(frame: &mut Frame, area: Rect, app: &mut AppState) {
-    let input_width: u16;
+    // position of the cursor in the text, the width of the prompt included
+    let cursor_x: usize;
+    let mut cursor_y: usize = 0;
 
-    let input_paragraph = match app.onboarding {
-        Onboarding::ConfirmingUsername => {
-            // dim the input text if input is still the generated username
-            let style = if app.input == app.user.username {
-                Style::new().italic().dim()
-            } else {
-                Style::default()
-            };
+    let input_paragraph = if let Some(search) = app.get_input_search() {
+        let (line, search_cursor_x) =
+            build_search_line(search.query.clone(), app.get_input_search_match());
+        cursor_x = search_cursor_x;
+        Paragraph::new(line)
+    } else {
+        match app.onboarding {
+            Onboarding::ConfirmingUsername => {
+                // dim the input text if input is still the generated username
+                let style = if app.input.as_str() == app.user.username {
+                    Style::new().italic().dim()
+                } else {
+                    Style::default()
+                };
 
-            let line = Line::from(vec![
-                Span::raw("Choose a username"),
-                Span::styled(" @ ", Style::default().italic()),
-                Span::styled(app.input.clone(), style),
-            ]);
-            input_width = line.width() as u16;
-            Paragraph::new(line)
-        }
-        Onboarding::ConfirmingRoom => {
-            // dim the input if room is still the generated room name
-            let style = if app.input == app.room {
-                Style::new().italic().dim()
-            } else {
-                Style::default()
-            };
+                let prompt = Line::from(vec![
+                    Span::raw("Choose a username"),
+                    Span::styled(" @ ", Style::default().italic()),
+                ]);
+                cursor_x = prompt.width() + app.input.cursor_position().1;
 
-            let line = Line::from(vec![
-                Span::raw("Enter a room name"),
-                Span::styled(" # ", Style::default().italic()),
-                Span::styled(app.input.clone(), style),
-            ]);
-            input_width = line.width() as u16;
-            Paragraph::new(line)
-        }
-        Onboarding::Completed => {
-            if app.input.is_empty() {
-                input_width = 0;
-                let text = format!("Message #{}", app.room);
-                let span = Span::raw(text).style(Style::new().italic().dim());
-                Paragraph::new(span)
-            } else {
-                input_width = app.input.len() as u16;
+                let mut line = prompt;
+                line.spans.push(Span::styled(app.input.to_string(), style));
+                Paragraph::new(line)
+            }
+            Onboarding::ConfirmingRoom => {
+                // dim the input if room is still the generated room name
+                let style = if app.input.as_str() == app.room {
+                    Style::new().italic().dim()
+                } else {
+                    Style::default()
+                };
+
+                let prompt = Line::from(vec![
+                    Span::raw("Enter a room name"),
+                    Span::styled(" # ", Style::default().italic()),
+                ]);
+                cursor_x = prompt.width() + app.input.cursor_position().1;
 
-                if app.input.starts_with("/") {
-                    let command = app.input.trim()[1..].to_string();
-                    let line = Line::from(vec![
-                        Span::styled("/", Style::default().light_blue().bold().not_dim()),
-                        Span::raw(command),
-                    ]);
-                    Paragraph::new(line)
+                let mut line = prompt;
+                line.spans.push(Span::styled(app.input.to_string(), style));
+                Paragraph::new(line)
+            }
+            Onboarding::Completed => {
+                if app.input.is_empty() {
+                    cursor_x = 0;
+                    let text = format!("Message #{}", app.room);
+                    let span = Span::raw(text).style(Style::new().italic().dim());
+                    Paragraph::new(span)
                 } else {
-                    Paragraph::new(app.input.clone())
+                    (cursor_y, cursor_x) = app.input.cursor_position();
+
+                    // a line per line of the message, long lines scroll rather than wrap
+                    let lines: Vec<Line> = app
+                        .input
+                        .as_str()
+                        .split('\n')
+                        .enumerate()
+                        .map(|(index, line)| match line.strip_prefix('/') {
+                            Some(command) if index == 0 => Line::from(vec![
+                                Span::styled("/", get_accent_style().bold().not_dim()),
+                                Span::raw(command.to_string()),
+                            ]),
+                            _ => Line::raw(line.to_string()),
+                        })
+                        .collect();
+                    Paragraph::new(lines)
                 }
             }
         }
@@ -70,20 +89,63 @@ pub fn render_widget(frame: &mut Frame, area: Rect, app: &mut AppState) {
         Style::new().dim()
     };
 
-    // Horizontal scroll when input extends past area width
-    let area_width = area_width_minus_border(area);
-    let horizontal_scroll = if input_width > area_width {
-        input_width - area_width
-    } else {
-        0
-    };
+    // Scroll when the cursor would be past the area width, keeping a column for it, or below the
+    // area height
+    let area_width = area_width_minus_border(area) as usize;
+    let horizontal_scroll = (cursor_x + 1).saturating_sub(area_width);
+    let area_height = area_height_minus_border(area) as usize;
+    let vertical_scroll = (cursor_y + 1).saturating_sub(area_height);
 
-    let widget = input_paragraph.scroll((0, horizontal_scroll)).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .border_style(border_style),
-    );
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border_style);
+    if app.is_searching_input() {
+        block = block.title(" Ctrl-R: older, Enter: use, Esc: cancel ");
+    }
+
+    let widget = input_paragraph
+        .scroll((vertical_scroll as u16, horizontal_scroll as u16))
+        .block(block);
 
-    app.set_input_width(input_width);
+    app.set_input_cursor_position(
+        cursor_x.saturating_sub(horizontal_scroll) as u16,
+        cursor_y.saturating_sub(vertical_scroll) as u16,
+    );
     frame.render_widget(widget, area);
 }
+
+// e.g. "Search history / nick  /nick casper" with the query highlighted in the match, the
+// cursor goes after the query. A multi-line match is shown on one line, with "↵" between its
+// lines, the input only shows it as lines once it is used
+fn build_search_line(query: String, search_match: Option<&str>) -> (Line<'static>, usize) {
+    let mut spans = vec![
+        Span::raw("Search history"),
+        Span::styled(" / ", Style::default().italic()),
+        Span::styled(query.clone(), get_accent_style().bold()),
+    ];
+    let cursor_x = Line::from(spans.clone()).width();
+    spans.push(Span::raw("  "));
+
+    match search_match {
+        Some(line) => {
+            // the query has no newline, so it is found in the flattened line all the same
+            let line = line.replace('\n', "↵");
+            let (start, end) = line
+                .find(&query)
+                .map(|start| (start, start + query.len()))
+                .unwrap_or((0, 0));
+            spans.push(Span::raw(line[..start].to_string()));
+            spans.push(Span::styled(
+                line[start..end].to_string(),
+                get_accent_style().underlined(),
+            ));
+            spans.push(Span::raw(line[end..].to_string()));
+        }
+        None if !query.is_empty() => {
+            spans.push(Span::styled("no match", Style::new().italic().dim()));
+        }
+        None => (),
+    }
+
+    (Line::from(spans), cursor_x)
+}
@@ -1,10 +1,11 @@
 use super::scrolbar;
-use crate::app::message::Message as AppMessage;
+use crate::app::message::{DeliveryStatus, Message as AppMessage};
 use crate::app::AppState;
 use crate::ui::math::area_height_minus_border;
 use crate::ui::math::get_wrapped_line_counts;
-use crate::ui::styles::get_title_style;
+use crate::ui::styles::{get_accent_style, get_error_style, get_title_style, get_username_style};
 use crate::ui::symbols::*;
+use crate::ui::timestamps::{format_day_separator, format_timestamp, local_date};
 use ratatui::prelude::*;
 use ratatui::widgets::*;
 
@@ -12,31 +13,30 @@ pub fn render_widget(frame: &mut Frame, area: Rect, app: &mut AppState) {
     let messages = app.get_messages();
     let area_height = area_height_minus_border(area) as usize;
 
-    let lines: Vec<Line> = messages
-        .iter()
-        .map(|message| match message {
-            AppMessage::SystemInternal(message) => Line::from(Span::styled(
-                format!("{}", message),
-                Style::default().italic().dim(),
-            )),
+    let timestamp_format = app.ui_timestamp_format;
 
-            AppMessage::SystemPublic(message) => Line::from(Span::styled(
-                format!("{}", message),
-                Style::default().light_blue().italic(),
-            )),
-            AppMessage::User(message) => {
-                let username = message.username.clone();
-                let content = message.content.clone();
+    // a separator goes before the first message of each day
+    let mut lines: Vec<Line> = vec![];
+    let mut last_date = None;
+    for message in messages.iter() {
+        let date = local_date(message.sent_at());
+        if last_date != Some(date) {
+            last_date = Some(date);
+            lines.push(
+                Line::from(Span::styled(format_day_separator(date), Style::new().dim()))
+                    .alignment(Alignment::Center),
+            );
+        }
 
-                Line::from(vec![
-                    Span::styled(format!("{}: ", username), Style::default().light_green()),
-                    Span::raw(content),
-                ])
-            }
-        })
-        .collect();
+        let mut message_lines = build_message_lines(message);
+        if let Some(timestamp) = format_timestamp(timestamp_format, message.sent_at()) {
+            let timestamp = Span::styled(format!("{} ", timestamp), Style::new().dim());
+            message_lines[0].spans.insert(0, timestamp);
+        }
+        lines.extend(message_lines);
+    }
 
-    let wrapped_line_counts = get_wrapped_line_counts(area, &messages);
+    let wrapped_line_counts = get_wrapped_line_counts(area, &lines);
     let wrapped_line_count: usize = wrapped_line_counts.iter().sum();
 
     app.set_messages_line_length_and_area_height(wrapped_line_count, area_height);
@@ -63,7 +63,58 @@ pub fn render_widget(frame: &mut Frame, area: Rect, app: &mut AppState) {
         frame,
         area,
         app.ui_messages_area_height,
-        app.ui_messages_line_length,
-        app.ui_messages_scrollbar_position,
+        app.get_messages_line_length(),
+        app.get_scrollbar_position(),
     );
 }
+
+// A message has a line per line of its content, the first one prefixed with the username
+fn build_message_lines(message: &AppMessage) -> Vec<Line<'static>> {
+    match message {
+        AppMessage::SystemError(message) => {
+            build_content_lines(vec![], &message.to_string(), get_error_style())
+        }
+        AppMessage::SystemInternal(message) => build_content_lines(
+            vec![],
+            &message.to_string(),
+            Style::default().italic().dim(),
+        ),
+        AppMessage::SystemPublic(message) => {
+            build_content_lines(vec![], &message.to_string(), get_accent_style().italic())
+        }
+        AppMessage::User(message) => {
+            let username = message.username.clone();
+
+            // our own messages are dimmed until acknowledged, and red if not delivered
+            let content_style = match message.status {
+                DeliveryStatus::Pending => Style::default().dim(),
+                DeliveryStatus::Sent => Style::default(),
+                DeliveryStatus::Failed => get_error_style().crossed_out(),
+            };
+
+            let prefix = vec![Span::styled(
+                format!("{}: ", username),
+                get_username_style(),
+            )];
+            build_content_lines(prefix, &message.content, content_style)
+        }
+    }
+}
+
+// tabs have no width in the terminal, e.g. in a pasted stack trace, so they become spaces
+fn build_content_lines(
+    mut prefix: Vec<Span<'static>>,
+    content: &str,
+    style: Style,
+) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line> = content
+        .split('\n')
+        .map(|line| {
+            let line = line.trim_end_matches('\r').replace('\t', "    ");
+            Line::from(Span::styled(line, style))
+        })
+        .collect();
+    prefix.append(&mut lines[0].spans);
+    lines[0].spans = prefix;
+    lines
+}
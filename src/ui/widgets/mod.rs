@@ -3,7 +3,9 @@ pub mod help;
 pub mod input;
 pub mod logs;
 pub mod messages;
+pub mod paste_prompt;
 pub mod rooms;
 pub mod scrolbar;
 pub mod sidebars;
+pub mod user_details;
 pub mod users;
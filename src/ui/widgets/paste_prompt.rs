@@ -0,0 +1,45 @@
+use crate::app::{message_length, AppState};
+use crate::ui::math::centered_rect;
+use crate::ui::styles::{get_accent_style, get_title_style};
+use ratatui::prelude::*;
+use ratatui::widgets::*;
+
+pub fn render_widget(frame: &mut Frame, area: Rect, app: &AppState) {
+    let Some(text) = app.get_pending_paste() else {
+        return;
+    };
+    let message_count = app.get_pending_paste_messages().len();
+    let widget = build_widget(text, message_count, app.get_max_message_length());
+
+    let popup_area = centered_rect(area, 56, 8);
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(widget, popup_area);
+}
+
+fn build_widget(text: &str, message_count: usize, max_length: usize) -> Paragraph<'static> {
+    let lines = vec![
+        Line::raw(format!(
+            "The paste is {} characters, a message can have at most {}.",
+            message_length(text),
+            max_length
+        )),
+        Line::raw(""),
+        Line::from(vec![
+            Span::styled("Enter", get_accent_style()),
+            Span::raw(format!(": Send it as {} messages", message_count)),
+        ]),
+        Line::from(vec![
+            Span::styled("Any other key", get_accent_style()),
+            Span::raw(": Cancel the paste"),
+        ]),
+    ];
+
+    Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Paste ")
+            .title_alignment(Alignment::Center)
+            .title_style(get_title_style())
+            .padding(Padding::horizontal(1)),
+    )
+}
@@ -0,0 +1,62 @@
+use crate::app::user::UserDetails;
+use crate::app::AppState;
+use crate::ui::math::centered_rect;
+use crate::ui::styles::{get_accent_style, get_title_style};
+use crate::ui::symbols::*;
+use ratatui::prelude::*;
+use ratatui::widgets::*;
+
+pub fn render_widget(frame: &mut Frame, area: Rect, app: &AppState) {
+    let Some(user) = app.get_selected_user() else {
+        return;
+    };
+    let details = app.get_user_details(&user);
+    let widget = build_widget(&details);
+
+    // fit the longest line, plus borders and padding
+    let popup_area = centered_rect(area, 56, 10);
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(widget, popup_area);
+}
+
+fn build_widget(details: &UserDetails) -> Paragraph<'static> {
+    let online_since = match details.user.online_since() {
+        Some(online_since) => online_since.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => "unknown".to_string(),
+    };
+    let rooms_in_common = details
+        .rooms_in_common
+        .iter()
+        .map(|room| format!("{HASH_SYMBOL} {room}"))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let lines = vec![
+        detail_line("username", format!("@{}", details.user.username)),
+        detail_line("uuid", details.user.uuid.clone()),
+        detail_line("online since", online_since),
+        detail_line("rooms", rooms_in_common),
+        detail_line("devices", details.device_count.to_string()),
+        Line::raw(""),
+        Line::styled(
+            "Enter: Send a direct message, any other key: Close",
+            Style::new().dim(),
+        ),
+    ];
+
+    Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" User ")
+            .title_alignment(Alignment::Center)
+            .title_style(get_title_style())
+            .padding(Padding::horizontal(1)),
+    )
+}
+
+fn detail_line(label: &'static str, value: String) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(format!("{label:>12}: "), get_accent_style()),
+        Span::raw(value),
+    ])
+}
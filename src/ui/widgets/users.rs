@@ -1,32 +1,73 @@
 use crate::app::AppState;
+use crate::app::Focus;
+use crate::ui::styles::get_accent_style;
 use crate::ui::styles::get_selection_style;
 use crate::ui::styles::get_title_style;
+use crate::ui::symbols::*;
 use ratatui::prelude::*;
 use ratatui::widgets::*;
 
-pub fn render_widget(frame: &mut Frame, area: Rect, app: &AppState) {
-    let users = app.get_uuid_username_pairs();
+pub fn render_widget(frame: &mut Frame, area: Rect, app: &mut AppState) {
+    let users: Vec<(String, String, usize, Option<usize>)> = app
+        .get_uuid_username_pairs()
+        .into_iter()
+        .map(|(uuid, username)| {
+            let device_count = app.get_device_count(&uuid);
+            let unread_count = app.get_direct_unread_count(&uuid);
+            (uuid, username, device_count, unread_count)
+        })
+        .collect();
     let user_uuid = app.user.uuid.clone();
-    let widget = build_widget(user_uuid, &users);
-    frame.render_widget(widget, area);
+    let widget = build_widget(user_uuid, &users, app.ui_focus_area);
+    let selected_user = match app.ui_focus_area {
+        Focus::Users => app.get_selected_user_index(),
+        _ => None,
+    };
+    app.ui_user_table_state.select(selected_user);
+    frame.render_stateful_widget(widget, area, &mut app.ui_user_table_state);
 }
 
-fn build_widget(app_user_uuid: String, users: &Vec<(String, String)>) -> Table {
+// unread_count is None for users we have no direct conversation with, the device count is only
+// shown for users present from more than one device
+fn build_widget(
+    app_user_uuid: String,
+    users: &Vec<(String, String, usize, Option<usize>)>,
+    focus: Focus,
+) -> Table {
     let mut rows: Vec<Row> = vec![];
-    for (uuid, username) in users {
-        let username = format!("{username}");
+    for (uuid, username, device_count, unread_count) in users {
+        let username = if *device_count > 1 {
+            format!("{username} ({device_count})")
+        } else {
+            format!("{username}")
+        };
         let style = get_selection_style(&uuid, &app_user_uuid);
-        let row = Row::new(vec![username]).style(style);
+        let unread = match unread_count {
+            Some(count) if *count > 0 => format!("{UNREAD_SYMBOL}{count}"),
+            _ => "".to_string(),
+        };
+        let row = Row::new(vec![
+            Cell::from(username),
+            Cell::from(Span::styled(unread, get_accent_style())),
+        ])
+        .style(style);
         rows.push(row);
     }
 
-    Table::new(rows, [Constraint::Fill(1)])
+    let border_style = if focus == Focus::Users {
+        Style::new().not_dim()
+    } else {
+        Style::new().dim()
+    };
+
+    Table::new(rows, [Constraint::Fill(1), Constraint::Min(1)])
         .column_spacing(1)
         .flex(layout::Flex::Legacy)
+        .highlight_symbol("> ")
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_style(Style::new().dim())
+                .border_style(border_style)
                 .title(" @ Users ")
                 .title_style(get_title_style()),
         )